@@ -0,0 +1,317 @@
+from typing import Any, Callable, Dict, Iterable, List, Optional, Sequence, Tuple, Union, overload
+
+Span = Tuple[int, int]
+CodePoints = Iterable[Union[int, str]]
+
+
+class Engine:
+    """The matching engine a Regex uses, passed as `engine=` when compiling."""
+
+    AUTO: "Engine"
+    CONSTANT_TIME: "Engine"
+
+    @property
+    def name(self) -> str: ...
+
+
+class Semantics:
+    """What the character classes of a pattern mean, passed as `semantics=`."""
+
+    UNICODE: "Semantics"
+    ASCII: "Semantics"
+
+    @property
+    def name(self) -> str: ...
+
+
+class BudgetExhausted(RuntimeError): ...
+
+
+class Cancelled(RuntimeError): ...
+
+
+class PolicyError(ValueError): ...
+
+
+class LockTimeout(RuntimeError): ...
+
+
+class Budget:
+    def __init__(self, ms: Optional[int] = None, bytes: Optional[int] = None) -> None: ...
+
+    @property
+    def remaining_ms(self) -> Optional[int]: ...
+
+    @property
+    def remaining_bytes(self) -> Optional[int]: ...
+
+    @property
+    def exhausted(self) -> bool: ...
+
+
+class CancellationToken:
+    def __init__(self) -> None: ...
+
+    def cancel(self) -> None: ...
+
+    @property
+    def cancelled(self) -> bool: ...
+
+
+class ScanSummary:
+    @property
+    def total(self) -> int: ...
+
+    @property
+    def counts(self) -> List[int]: ...
+
+    @property
+    def examples(self) -> List[Dict[str, Any]]: ...
+
+    @property
+    def truncated(self) -> bool: ...
+
+    def to_dict(self) -> Dict[str, Any]: ...
+
+    @classmethod
+    def schema(cls) -> Dict[str, Any]: ...
+
+
+class Regex:
+    def __init__(
+        self,
+        pattern: str,
+        constant_time: bool = False,
+        pre: Optional[Sequence[str]] = None,
+        engine: Optional[Engine] = None,
+        semantics: Optional[Semantics] = None,
+    ) -> None: ...
+
+    def is_match(
+        self, other: str, budget: Optional[Budget] = None, token: Optional[CancellationToken] = None,
+    ) -> bool: ...
+
+    def is_match_at(
+        self,
+        other: str,
+        start: int,
+        budget: Optional[Budget] = None,
+        token: Optional[CancellationToken] = None,
+    ) -> bool: ...
+
+    def find(
+        self, other: str, budget: Optional[Budget] = None, token: Optional[CancellationToken] = None,
+    ) -> Optional[str]: ...
+
+    @overload
+    def findall(
+        self,
+        other: str,
+        budget: Optional[Budget] = None,
+        token: Optional[CancellationToken] = None,
+        summary: None = None,
+    ) -> List[str]: ...
+    @overload
+    def findall(
+        self,
+        other: str,
+        budget: Optional[Budget] = None,
+        token: Optional[CancellationToken] = None,
+        *,
+        summary: int,
+    ) -> ScanSummary: ...
+
+    def all_captures(
+        self, other: str, budget: Optional[Budget] = None, token: Optional[CancellationToken] = None,
+    ) -> List[List[Optional[str]]]: ...
+
+    def captures(
+        self, other: str, budget: Optional[Budget] = None, token: Optional[CancellationToken] = None,
+    ) -> Optional[List[Optional[str]]]: ...
+
+    @overload
+    def matches(
+        self,
+        other: str,
+        budget: Optional[Budget] = None,
+        token: Optional[CancellationToken] = None,
+        doc_id: None = None,
+        summary: None = None,
+    ) -> List[Span]: ...
+    @overload
+    def matches(
+        self,
+        other: str,
+        budget: Optional[Budget] = None,
+        token: Optional[CancellationToken] = None,
+        *,
+        doc_id: str,
+    ) -> List[Tuple[int, int, str]]: ...
+    @overload
+    def matches(
+        self,
+        other: str,
+        budget: Optional[Budget] = None,
+        token: Optional[CancellationToken] = None,
+        *,
+        summary: int,
+    ) -> ScanSummary: ...
+
+    def match_within_group(
+        self,
+        other: str,
+        group: Union[int, str],
+        inner_pattern: Union["Regex", str],
+        budget: Optional[Budget] = None,
+        token: Optional[CancellationToken] = None,
+    ) -> List[Span]: ...
+
+    def is_match_codepoints(
+        self, codepoints: CodePoints, budget: Optional[Budget] = None, token: Optional[CancellationToken] = None,
+    ) -> bool: ...
+
+    def matches_codepoints(
+        self, codepoints: CodePoints, budget: Optional[Budget] = None, token: Optional[CancellationToken] = None,
+    ) -> List[Span]: ...
+
+    @overload
+    def search_file(
+        self,
+        path: str,
+        encoding: str = "auto",
+        budget: Optional[Budget] = None,
+        token: Optional[CancellationToken] = None,
+        summary: None = None,
+    ) -> List[Span]: ...
+    @overload
+    def search_file(
+        self,
+        path: str,
+        encoding: str = "auto",
+        budget: Optional[Budget] = None,
+        token: Optional[CancellationToken] = None,
+        *,
+        summary: int,
+    ) -> ScanSummary: ...
+
+    def replace(
+        self, other: str, rep: str, budget: Optional[Budget] = None, token: Optional[CancellationToken] = None,
+    ) -> str: ...
+
+    def replace_all(
+        self, other: str, rep: str, budget: Optional[Budget] = None, token: Optional[CancellationToken] = None,
+    ) -> str: ...
+
+    def replacen(
+        self,
+        other: str,
+        limit: int,
+        rep: str,
+        budget: Optional[Budget] = None,
+        token: Optional[CancellationToken] = None,
+    ) -> str: ...
+
+    def validate_template(self, template: str) -> List[Dict[str, Any]]: ...
+
+    def format_matches(
+        self,
+        other: str,
+        template: str,
+        budget: Optional[Budget] = None,
+        token: Optional[CancellationToken] = None,
+    ) -> str: ...
+
+    def tokenize(
+        self, other: str, budget: Optional[Budget] = None, token: Optional[CancellationToken] = None,
+    ) -> List[Tuple[str, str, int, int]]: ...
+
+    def top_matches(
+        self, texts: Union[str, Iterable[str]], k: int = 10, token: Optional[CancellationToken] = None,
+    ) -> List[Tuple[str, int]]: ...
+
+    def fingerprint(self) -> str: ...
+
+    def coverage(self, texts: Iterable[str], token: Optional[CancellationToken] = None) -> Dict[str, Any]: ...
+
+
+class RegexSet:
+    def __init__(self, pattern: Sequence[str], semantics: Optional[Semantics] = None) -> None: ...
+
+    def is_match(
+        self, other: str, budget: Optional[Budget] = None, token: Optional[CancellationToken] = None,
+    ) -> bool: ...
+
+    def find(
+        self, other: str, budget: Optional[Budget] = None, token: Optional[CancellationToken] = None,
+    ) -> List[int]: ...
+
+    @overload
+    def classify_file(
+        self, path: str, token: Optional[CancellationToken] = None, summary: None = None,
+    ) -> List[Tuple[int, Optional[int]]]: ...
+    @overload
+    def classify_file(
+        self, path: str, token: Optional[CancellationToken] = None, *, summary: int,
+    ) -> ScanSummary: ...
+
+    def profile(
+        self, corpus: Iterable[str], every: int = 1, token: Optional[CancellationToken] = None,
+    ) -> List[Dict[str, Any]]: ...
+
+
+class PathIndex:
+    def __init__(self, paths: Iterable[str], case_insensitive: bool = True) -> None: ...
+
+    def search(self, pattern: str, token: Optional[CancellationToken] = None) -> List[str]: ...
+
+    def __len__(self) -> int: ...
+
+
+class CidrMatcher:
+    def is_match(self, other: str, token: Optional[CancellationToken] = None) -> bool: ...
+
+    def findall(self, other: str, token: Optional[CancellationToken] = None) -> List[str]: ...
+
+    def matches(self, other: str, token: Optional[CancellationToken] = None) -> List[Span]: ...
+
+
+def matches(regex_pattern: str, other: str, token: Optional[CancellationToken] = None) -> List[Span]: ...
+
+def register_macro(name: str, expander: Callable[[str], str]) -> None: ...
+
+def set_policy(
+    max_groups: Optional[int] = None,
+    max_pattern_len: Optional[int] = None,
+    max_alternations: Optional[int] = None,
+) -> None: ...
+
+def get_policy() -> Dict[str, Optional[int]]: ...
+
+def strip_ansi(text: str) -> Tuple[str, List[int]]: ...
+
+def strip_control(text: str) -> Tuple[str, List[int]]: ...
+
+def flag_analysis(pattern: str) -> List[Dict[str, Any]]: ...
+
+def detokenize(tokens: Iterable[Tuple[str, str, int, int]]) -> str: ...
+
+def ip_in_cidr_matcher(cidrs: Sequence[str]) -> CidrMatcher: ...
+
+def scan_stdin(pattern: str, format: str = "jsonl", token: Optional[CancellationToken] = None) -> int: ...
+
+@overload
+def schema(name: None = None) -> Dict[str, Dict[str, Any]]: ...
+@overload
+def schema(name: str) -> Dict[str, Any]: ...
+
+def lock_status() -> Dict[str, bool]: ...
+
+def set_lock_timeout(ms: int) -> None: ...
+
+def compare_with_re(
+    pattern: str, haystack: str, iterations: int = 100, token: Optional[CancellationToken] = None,
+) -> Dict[str, Any]: ...
+
+def metrics_text() -> str: ...
+
+def features() -> Dict[str, bool]: ...
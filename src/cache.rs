@@ -0,0 +1,54 @@
+use pyo3::prelude::*;
+
+use regex::Regex;
+
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock};
+
+use crate::engine::SemanticsKind;
+use crate::guard::Guarded;
+use crate::metrics;
+
+
+/// The most patterns kept compiled, the cache is emptied once it is full
+/// rather than tracking which patterns were used least recently.
+const CAPACITY: usize = 256;
+
+/// Patterns compiled by the module level functions, keyed by the pattern as
+/// it was given before macros were expanded.
+pub fn cache() -> &'static Guarded<HashMap<String, Arc<Regex>>> {
+    static CACHE: OnceLock<Guarded<HashMap<String, Arc<Regex>>>> = OnceLock::new();
+    CACHE.get_or_init(|| Guarded::new("cache", HashMap::new()))
+}
+
+/// Returns the compiled form of a pattern, compiling it if it isn't cached.
+pub fn get_or_compile(py: Python, pattern: &str) -> PyResult<Arc<Regex>> {
+    if let Some(regex) = cache().lock()?.get(pattern) {
+        metrics::CACHE_HITS.inc();
+        return Ok(regex.clone());
+    }
+    metrics::CACHE_MISSES.inc();
+
+    // Expanding macros calls into Python, so the lock can't be held while
+    // compiling.
+    let expanded = crate::expand_pattern(py, pattern, SemanticsKind::Unicode)?;
+    let regex = match Regex::new(&expanded) {
+        Ok(r) => Arc::new(r),
+        Err(e) => return Err(crate::compile_error(e)),
+    };
+
+    let mut cache = cache().lock()?;
+    if cache.len() >= CAPACITY {
+        cache.clear();
+    }
+    cache.insert(pattern.to_string(), regex.clone());
+
+    Ok(regex)
+}
+
+/// Empties the cache, called whenever something changes how patterns are
+/// compiled like a macro being registered or the policy changing.
+pub fn clear() -> PyResult<()> {
+    cache().lock()?.clear();
+    Ok(())
+}
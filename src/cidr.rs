@@ -0,0 +1,232 @@
+use pyo3::prelude::*;
+use pyo3::exceptions::PyValueError;
+
+use regex::Regex;
+
+use std::collections::{BTreeMap, HashSet};
+use std::net::{IpAddr, Ipv4Addr};
+
+use crate::limits::{CancellationToken, Limits};
+use crate::metrics;
+
+
+/// Runs of characters that can make up an IPv4 or IPv6 address, every run
+/// is then parsed to see if it really is one.
+const CANDIDATE_PATTERN: &str = r"[0-9A-Fa-f:.]*[:.][0-9A-Fa-f:.]*";
+
+/// Networks of one address family grouped by prefix length, so checking an
+/// address is a lookup per distinct prefix length rather than per network.
+struct Networks<T> {
+    by_prefix: BTreeMap<u32, HashSet<T>>,
+}
+
+impl<T> Networks<T>
+where
+    T: Copy + Eq + std::hash::Hash + std::ops::BitAnd<Output = T> + std::ops::Not<Output = T>
+        + std::ops::Shr<u32, Output = T> + From<u8>,
+{
+    fn new() -> Self {
+        Networks { by_prefix: BTreeMap::new() }
+    }
+
+    fn mask(prefix: u32) -> T {
+        let ones = !T::from(0);
+        match prefix {
+            0 => T::from(0),
+            p if p as usize >= std::mem::size_of::<T>() * 8 => ones,
+
+            // Shifting all ones right leaves the host bits set, inverting
+            // that gives the network bits.
+            p => !(ones >> p),
+        }
+    }
+
+    fn insert(&mut self, addr: T, prefix: u32) {
+        let network = addr & Self::mask(prefix);
+        self.by_prefix.entry(prefix).or_default().insert(network);
+    }
+
+    fn contains(&self, addr: T) -> bool {
+        self.by_prefix
+            .iter()
+            .any(|(prefix, networks)| networks.contains(&(addr & Self::mask(*prefix))))
+    }
+}
+
+/// Finds IP addresses in text that fall within a set of CIDR ranges, made
+/// with `ip_in_cidr_matcher`.
+///
+/// Addresses are first found by their shape and then parsed and checked
+/// against the ranges natively, which plain regex can't express. Both IPv4
+/// and IPv6 addresses are supported, IPv4 addresses followed by a port like
+/// `10.0.0.1:8080` are matched without the port, addresses after a key like
+/// `addr:10.0.0.1` are matched without the key and IPv4 mapped IPv6
+/// addresses are checked against the IPv4 ranges.
+#[pyclass]
+pub struct CidrMatcher {
+    candidates: Regex,
+    v4: Networks<u32>,
+    v6: Networks<u128>,
+}
+
+impl CidrMatcher {
+    pub fn new(cidrs: Vec<&str>) -> PyResult<Self> {
+        let mut v4 = Networks::new();
+        let mut v6 = Networks::new();
+
+        for cidr in cidrs {
+            let invalid = || PyValueError::new_err(format!("invalid CIDR range {:?}", cidr));
+
+            let (addr, prefix) = match cidr.split_once('/') {
+                Some((addr, prefix)) => (addr, Some(prefix.parse::<u32>().map_err(|_| invalid())?)),
+                None => (cidr, None),
+            };
+
+            match addr.parse::<IpAddr>().map_err(|_| invalid())? {
+                IpAddr::V4(addr) => {
+                    let prefix = prefix.unwrap_or(32);
+                    if prefix > 32 {
+                        return Err(invalid());
+                    }
+                    v4.insert(u32::from(addr), prefix);
+                },
+                IpAddr::V6(addr) => {
+                    let prefix = prefix.unwrap_or(128);
+                    if prefix > 128 {
+                        return Err(invalid());
+                    }
+                    v6.insert(u128::from(addr), prefix);
+                },
+            }
+        }
+
+        let candidates = Regex::new(CANDIDATE_PATTERN).unwrap();
+        Ok(CidrMatcher { candidates, v4, v6 })
+    }
+
+    fn contains(&self, addr: IpAddr) -> bool {
+        match addr {
+            IpAddr::V4(addr) => self.v4.contains(u32::from(addr)),
+            IpAddr::V6(addr) => match addr.to_ipv4_mapped() {
+                Some(mapped) => self.v4.contains(u32::from(mapped)),
+                None => self.v6.contains(u128::from(addr)),
+            },
+        }
+    }
+
+    /// Finds the spans of every address within the ranges.
+    fn find_spans(&self, text: &str, limits: &Limits) -> PyResult<Vec<(usize, usize)>> {
+        let bytes = text.as_bytes();
+        let is_word = |b: u8| b.is_ascii_alphanumeric() || b == b'_';
+
+        limits.start(text.len())?;
+
+        let mut spans = Vec::new();
+        for m in self.candidates.find_iter(text) {
+            limits.check()?;
+
+            // Addresses glued to other words are part of something else.
+            if m.end() < bytes.len() && is_word(bytes[m.end()]) {
+                continue;
+            }
+            let glued = m.start() > 0 && is_word(bytes[m.start() - 1]);
+
+            // Sentences can start or end right next to an address.
+            let run = m.as_str();
+            let trimmed = run.trim_start_matches('.');
+            let start = m.start() + (run.len() - trimmed.len());
+            let run = trimmed.trim_end_matches('.');
+
+            let found = if glued {
+                None
+            } else {
+                parse_run(run).map(|(addr, len)| (addr, start, len))
+            };
+
+            // A `key:10.0.0.1` run starts with the colon when the key has
+            // letters that can't be hex, or takes in the whole key when it
+            // is all hex letters, either way the address follows the colon.
+            let found = found.or_else(|| {
+                let colon = run.find(':')?;
+                parse_run(&run[colon + 1..]).map(|(addr, len)| (addr, start + colon + 1, len))
+            });
+
+            if let Some((addr, start, len)) = found {
+                if self.contains(addr) {
+                    spans.push((start, start + len));
+                }
+            }
+        }
+
+        metrics::MATCHES.add(spans.len());
+        Ok(spans)
+    }
+}
+
+/// Parses a run as an address, or as an IPv4 address followed by a port,
+/// returning the address and its length within the run.
+fn parse_run(run: &str) -> Option<(IpAddr, usize)> {
+    match run.parse::<IpAddr>() {
+        Ok(addr) => Some((addr, run.len())),
+        Err(_) => run
+            .split_once(':')
+            .and_then(|(host, _)| host.parse::<Ipv4Addr>().ok())
+            .map(|addr| (IpAddr::V4(addr), run.find(':').unwrap())),
+    }
+}
+
+#[pymethods]
+impl CidrMatcher {
+    /// Checks if the string contains any address within the ranges.
+    ///
+    /// Args:
+    ///     other:
+    ///         The string to search for addresses.
+    ///     token:
+    ///         An optional CancellationToken that can cancel this call.
+    ///
+    /// Returns:
+    ///     A bool signifying if it is a match or not.
+    #[args(token = "None")]
+    fn is_match(&self, py: Python, other: &str, token: Option<PyRef<CancellationToken>>) -> PyResult<bool> {
+        let limits = Limits::new(None, token.as_deref());
+        let spans = py.allow_threads(|| self.find_spans(other, &limits))?;
+        Ok(!spans.is_empty())
+    }
+
+    /// Finds every address within the ranges in the string.
+    ///
+    /// Args:
+    ///     other:
+    ///         The string to search for addresses.
+    ///     token:
+    ///         An optional CancellationToken that can cancel this call.
+    ///
+    /// Returns:
+    ///     A list of the matching addresses as they appear in the string.
+    #[args(token = "None")]
+    fn findall(&self, py: Python, other: &str, token: Option<PyRef<CancellationToken>>) -> PyResult<Vec<String>> {
+        let limits = Limits::new(None, token.as_deref());
+        let spans = py.allow_threads(|| self.find_spans(other, &limits))?;
+        Ok(spans
+            .into_iter()
+            .map(|(start, end)| other[start..end].to_string())
+            .collect())
+    }
+
+    /// Finds the position of every address within the ranges in the string.
+    ///
+    /// Args:
+    ///     other:
+    ///         The string to search for addresses.
+    ///     token:
+    ///         An optional CancellationToken that can cancel this call.
+    ///
+    /// Returns:
+    ///     A vector of tuples that contain (start_match, end_match+1).
+    #[args(token = "None")]
+    fn matches(&self, py: Python, other: &str, token: Option<PyRef<CancellationToken>>) -> PyResult<Vec<(usize, usize)>> {
+        let limits = Limits::new(None, token.as_deref());
+        py.allow_threads(|| self.find_spans(other, &limits))
+    }
+}
@@ -0,0 +1,90 @@
+use pyo3::prelude::*;
+
+use regex::Regex;
+
+use std::time::{Duration, Instant};
+
+use crate::limits::Limits;
+use crate::metrics;
+
+
+/// How this crate and Python's `re` compare on a pattern and haystack.
+pub struct Comparison {
+    /// The total time this crate took over every iteration.
+    pub time: Duration,
+
+    /// The total time `re` took over every iteration.
+    pub re_time: Duration,
+
+    /// The (start, end) char offsets of every match found by this crate.
+    pub spans: Vec<(usize, usize)>,
+
+    /// The (start, end) char offsets of every match found by `re`.
+    pub re_spans: Vec<(usize, usize)>,
+}
+
+impl Comparison {
+    /// The index of the first match the engines disagree on, if any.
+    pub fn first_difference(&self) -> Option<usize> {
+        let shared = self.spans.len().min(self.re_spans.len());
+        (0..shared)
+            .find(|&i| self.spans[i] != self.re_spans[i])
+            .or(if self.spans.len() != self.re_spans.len() { Some(shared) } else { None })
+    }
+}
+
+/// Finds every match of the pattern in the haystack `iterations` times with
+/// both engines, `re` compiles the pattern the regex was compiled from so
+/// both run the same pattern after macros are expanded.
+///
+/// Both engines do the same work in every iteration, finding every match
+/// and collecting them into a list, so neither has to convert its matches
+/// while it is being timed. The GIL is released while this crate is timed
+/// and the limits are checked between iterations.
+pub fn run(
+    py: Python,
+    regex: &Regex,
+    haystack: &str,
+    iterations: usize,
+    limits: &Limits,
+) -> PyResult<Comparison> {
+    let compiled = py.import("re")?.call1("compile", (regex.as_str(),))?;
+    let finditer = compiled.getattr("finditer")?;
+    let list = py.import("builtins")?.getattr("list")?;
+
+    let (time, spans) = py.allow_threads(|| -> PyResult<_> {
+        let mut spans = Vec::new();
+        let start = Instant::now();
+        for _ in 0..iterations {
+            limits.check()?;
+            spans = regex.find_iter(haystack).map(|m| (m.start(), m.end())).collect();
+        }
+
+        Ok((start.elapsed(), spans))
+    })?;
+    metrics::SEARCHES.add(iterations);
+    metrics::BYTES_SCANNED.add(haystack.len() * iterations);
+    metrics::MATCHES.add(spans.len() * iterations);
+
+    let mut matches = None;
+    let start = Instant::now();
+    for _ in 0..iterations {
+        limits.check()?;
+        matches = Some(list.call1((finditer.call1((haystack,))?,))?);
+    }
+    let re_time = start.elapsed();
+
+    let mut re_spans = Vec::new();
+    if let Some(matches) = matches {
+        for m in matches.iter()? {
+            re_spans.push(m?.call_method0("span")?.extract()?);
+        }
+    }
+
+    Ok(Comparison {
+        time,
+        re_time,
+        spans: crate::char_spans(haystack, spans),
+        re_spans,
+    })
+}
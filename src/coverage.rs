@@ -0,0 +1,198 @@
+use pyo3::prelude::*;
+
+use regex::Regex;
+use regex_syntax::ast::{Ast, CaptureName, Concat, Group, GroupKind, Span};
+use regex_syntax::ast::parse::Parser;
+use regex_syntax::ast::print::Printer;
+
+use std::mem;
+
+use crate::limits::Limits;
+
+
+/// Prefix of the capture groups wrapped around every alternation branch,
+/// underscores are added to it until it doesn't appear in the pattern.
+const BRANCH_PREFIX: &str = "__coverage_branch_";
+
+/// A pattern instrumented so every alternation branch is wrapped in its own
+/// named capture group, letting captures tell which branches matched.
+pub struct Instrumented {
+    regex: Regex,
+
+    /// The span of every branch in the original pattern, by branch id.
+    branches: Vec<Span>,
+
+    /// The capture group slot of every branch, by branch id.
+    branch_slots: Vec<usize>,
+
+    /// The capture group slot and name of every group in the original
+    /// pattern, in the original index order.
+    groups: Vec<(usize, Option<String>)>,
+}
+
+pub struct BranchCoverage {
+    pub span: Span,
+    pub hits: usize,
+}
+
+pub struct GroupCoverage {
+    pub index: usize,
+    pub name: Option<String>,
+    pub hits: usize,
+}
+
+pub struct Report {
+    pub texts: usize,
+    pub matches: usize,
+
+    /// Branches in the order they appear in the pattern.
+    pub branches: Vec<BranchCoverage>,
+    pub groups: Vec<GroupCoverage>,
+}
+
+impl Instrumented {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let mut ast = Parser::new()
+            .parse(pattern)
+            .map_err(|e| e.to_string())?;
+
+        // Group names in the pattern can't start with a prefix the pattern
+        // doesn't contain, so only generated groups are taken as branches.
+        let mut prefix = String::from(BRANCH_PREFIX);
+        while pattern.contains(&prefix) {
+            prefix.push('_');
+        }
+
+        let mut branches = Vec::new();
+        instrument(&mut ast, &prefix, &mut branches);
+
+        let mut instrumented = String::new();
+        Printer::new()
+            .print(&ast, &mut instrumented)
+            .map_err(|e| e.to_string())?;
+
+        let regex = Regex::new(&instrumented).map_err(|e| e.to_string())?;
+
+        let mut branch_slots = vec![0; branches.len()];
+        let mut groups = Vec::new();
+        for (slot, name) in regex.capture_names().enumerate().skip(1) {
+            let branch = name
+                .and_then(|n| n.strip_prefix(prefix.as_str()))
+                .and_then(|id| id.parse::<usize>().ok());
+
+            match branch {
+                Some(id) => branch_slots[id] = slot,
+                None => groups.push((slot, name.map(String::from))),
+            }
+        }
+
+        Ok(Instrumented { regex, branches, branch_slots, groups })
+    }
+
+    /// Runs the instrumented pattern over every text, counting how many
+    /// matches each branch and group participated in.
+    pub fn run<'a>(&self, texts: impl Iterator<Item = &'a str>, limits: &Limits) -> PyResult<Report> {
+        let mut branch_hits = vec![0; self.branches.len()];
+        let mut group_hits = vec![0; self.groups.len()];
+        let mut text_count = 0;
+        let mut matches = 0;
+
+        for text in texts {
+            limits.start(text.len())?;
+            text_count += 1;
+            for caps in self.regex.captures_iter(text) {
+                limits.check()?;
+                matches += 1;
+                for (id, slot) in self.branch_slots.iter().enumerate() {
+                    if caps.get(*slot).is_some() {
+                        branch_hits[id] += 1;
+                    }
+                }
+                for (i, (slot, _)) in self.groups.iter().enumerate() {
+                    if caps.get(*slot).is_some() {
+                        group_hits[i] += 1;
+                    }
+                }
+            }
+        }
+
+        let mut branches: Vec<BranchCoverage> = self.branches
+            .iter()
+            .zip(branch_hits)
+            .map(|(span, hits)| BranchCoverage { span: *span, hits })
+            .collect();
+        branches.sort_by_key(|b| (b.span.start.offset, b.span.end.offset));
+
+        let groups = self.groups
+            .iter()
+            .zip(group_hits)
+            .enumerate()
+            .map(|(i, ((_, name), hits))| GroupCoverage {
+                index: i + 1,
+                name: name.clone(),
+                hits,
+            })
+            .collect();
+
+        Ok(Report { texts: text_count, matches, branches, groups })
+    }
+}
+
+/// Wraps every alternation branch in `ast` in a named capture group,
+/// recording the original span of each branch.
+fn instrument(ast: &mut Ast, prefix: &str, branches: &mut Vec<Span>) {
+    match ast {
+        Ast::Repetition(rep) => instrument(&mut rep.ast, prefix, branches),
+        Ast::Group(group) => instrument(&mut group.ast, prefix, branches),
+        Ast::Concat(concat) => {
+            for ast in concat.asts.iter_mut() {
+                instrument(ast, prefix, branches);
+            }
+        },
+        Ast::Alternation(alt) => {
+            // Flags set inside a branch carry on into the following branches,
+            // wrapping the branch in a group would end them early so they are
+            // repeated at the start of every following branch instead.
+            let mut carried: Vec<Ast> = Vec::new();
+            for branch in alt.asts.iter_mut() {
+                instrument(branch, prefix, branches);
+
+                let span = *branch.span();
+                let set_flags = top_level_flags(branch);
+
+                let mut inner = mem::replace(branch, Ast::empty(span));
+                if !carried.is_empty() {
+                    let mut asts = carried.clone();
+                    asts.push(inner);
+                    inner = Ast::concat(Concat { span, asts });
+                }
+                carried.extend(set_flags);
+
+                let name = CaptureName {
+                    span,
+                    name: format!("{}{}", prefix, branches.len()),
+                    index: 0,
+                };
+                *branch = Ast::group(Group {
+                    span,
+                    kind: GroupKind::CaptureName { starts_with_p: true, name },
+                    ast: Box::new(inner),
+                });
+                branches.push(span);
+            }
+        },
+        _ => {},
+    }
+}
+
+fn top_level_flags(ast: &Ast) -> Vec<Ast> {
+    match ast {
+        Ast::Flags(_) => vec![ast.clone()],
+        Ast::Concat(concat) => concat.asts
+            .iter()
+            .filter(|ast| matches!(ast, Ast::Flags(_)))
+            .cloned()
+            .collect(),
+        _ => Vec::new(),
+    }
+}
@@ -0,0 +1,83 @@
+/// The text encodings files can be decoded from.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+}
+
+/// How many bytes from the start of a file are looked at when guessing a
+/// UTF-16 file without a byte order mark.
+const SNIFF_LEN: usize = 4096;
+
+impl Encoding {
+    pub const ALL: [Encoding; 3] = [Encoding::Utf8, Encoding::Utf16Le, Encoding::Utf16Be];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "utf-8",
+            Encoding::Utf16Le => "utf-16-le",
+            Encoding::Utf16Be => "utf-16-be",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        let name = name.to_ascii_lowercase().replace('_', "-");
+        Encoding::ALL.iter().copied().find(|e| e.name() == name)
+    }
+
+    /// Detects the encoding of `bytes`, returning it along with the length
+    /// of the byte order mark it starts with.
+    ///
+    /// Without a byte order mark UTF-16 is recognised by how often the
+    /// bytes on one side of each pair are NUL, which they nearly always are
+    /// for mostly ASCII text like logs.
+    pub fn detect(bytes: &[u8]) -> (Self, usize) {
+        if bytes.starts_with(&[0xef, 0xbb, 0xbf]) {
+            return (Encoding::Utf8, 3);
+        }
+        if bytes.starts_with(&[0xff, 0xfe]) {
+            return (Encoding::Utf16Le, 2);
+        }
+        if bytes.starts_with(&[0xfe, 0xff]) {
+            return (Encoding::Utf16Be, 2);
+        }
+
+        let sample = &bytes[..bytes.len().min(SNIFF_LEN) & !1];
+        let pairs = sample.len() / 2;
+        let even = sample.iter().step_by(2).filter(|b| **b == 0).count();
+        let odd = sample.iter().skip(1).step_by(2).filter(|b| **b == 0).count();
+
+        let encoding = if pairs > 0 && odd * 4 >= pairs && even * 10 <= odd {
+            Encoding::Utf16Le
+        } else if pairs > 0 && even * 4 >= pairs && odd * 10 <= even {
+            Encoding::Utf16Be
+        } else {
+            Encoding::Utf8
+        };
+
+        (encoding, 0)
+    }
+
+    /// Decodes `bytes`, replacing invalid sequences with U+FFFD the same way
+    /// Python's `errors="replace"` does.
+    pub fn decode(self, bytes: &[u8]) -> String {
+        let unit = match self {
+            Encoding::Utf8 => return String::from_utf8_lossy(bytes).into_owned(),
+            Encoding::Utf16Le => u16::from_le_bytes,
+            Encoding::Utf16Be => u16::from_be_bytes,
+        };
+
+        let units = bytes.chunks_exact(2).map(|pair| unit([pair[0], pair[1]]));
+        let mut text: String = char::decode_utf16(units)
+            .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+            .collect();
+
+        // A trailing odd byte is half a code unit.
+        if bytes.len() % 2 == 1 {
+            text.push(char::REPLACEMENT_CHARACTER);
+        }
+
+        text
+    }
+}
@@ -0,0 +1,281 @@
+use pyo3::prelude::*;
+use pyo3::basic::CompareOp;
+use pyo3::class::PyObjectProtocol;
+use pyo3::exceptions::PyTypeError;
+use pyo3::PyNativeType;
+
+use regex_automata::util::captures::Captures as MetaCaptures;
+use regex_syntax::ast::{Ast, AssertionKind, ClassPerl, ClassPerlKind, ClassSet, ClassSetItem, Span};
+use regex_syntax::ast::parse::Parser;
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+
+/// Generates the shared parts of an enum exposed to Python: the class
+/// attribute for every variant, `name`, `repr`, equality and hashing, and
+/// extracting a variant from an argument with a clear error.
+macro_rules! py_enum {
+    ($class:ident, $kind:ident, $arg:literal, { $($variant:ident => $name:ident),+ $(,)? }) => {
+        #[pymethods]
+        impl $class {
+            $(
+                #[classattr]
+                #[allow(non_snake_case)]
+                fn $name() -> Self {
+                    $class { kind: $kind::$variant }
+                }
+            )+
+
+            /// The name of the variant.
+            #[getter]
+            fn name(&self) -> &'static str {
+                self.kind.name()
+            }
+        }
+
+        impl $kind {
+            pub const ALL: &'static [$kind] = &[$($kind::$variant),+];
+
+            pub fn name(self) -> &'static str {
+                match self {
+                    $($kind::$variant => stringify!($name)),+
+                }
+            }
+        }
+
+        impl $class {
+            /// Extracts the variant passed as an argument, only accepting
+            /// instances of the class so misspelt strings can't slip through.
+            pub fn extract(value: Option<&PyAny>) -> PyResult<Option<$kind>> {
+                let value = match value {
+                    Some(value) if !value.is_none() => value,
+                    _ => return Ok(None),
+                };
+
+                if let Ok(variant) = value.extract::<PyRef<$class>>() {
+                    return Ok(Some(variant.kind));
+                }
+
+                let names: Vec<String> = $kind::ALL
+                    .iter()
+                    .map(|kind| format!("{}.{}", stringify!($class), kind.name()))
+                    .collect();
+                Err(PyTypeError::new_err(format!(
+                    "{} must be one of {}, got {} {}",
+                    $arg,
+                    names.join(", "),
+                    value.get_type().name(),
+                    value.repr()?,
+                )))
+            }
+        }
+
+        #[pyproto]
+        impl PyObjectProtocol for $class {
+            fn __repr__(&self) -> String {
+                format!("{}.{}", stringify!($class), self.kind.name())
+            }
+
+            fn __hash__(&self) -> u64 {
+                let mut hasher = DefaultHasher::new();
+                self.kind.name().hash(&mut hasher);
+                hasher.finish()
+            }
+
+            fn __richcmp__(&self, other: &PyAny, op: CompareOp) -> PyObject {
+                let py = other.py();
+                let other = match other.extract::<PyRef<$class>>() {
+                    Ok(other) => other.kind,
+                    Err(_) => return py.NotImplemented(),
+                };
+
+                match op {
+                    CompareOp::Eq => (self.kind == other).into_py(py),
+                    CompareOp::Ne => (self.kind != other).into_py(py),
+                    _ => py.NotImplemented(),
+                }
+            }
+        }
+    };
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum EngineKind {
+    Auto,
+    ConstantTime,
+}
+
+/// The matching engine a Regex uses, passed as `engine=` when compiling.
+///
+///     AUTO - Picks the fastest strategy for the pattern, the default.
+///     CONSTANT_TIME - Disables the literal prefilter optimizations and
+///         scans the whole string even after the first match is found, so
+///         the matching time leaks less about partially matching input.
+///         Every search uses it, only `coverage` isn't supported.
+#[pyclass]
+#[derive(Clone)]
+pub struct Engine {
+    kind: EngineKind,
+}
+
+py_enum!(Engine, EngineKind, "engine", {
+    Auto => AUTO,
+    ConstantTime => CONSTANT_TIME,
+});
+
+/// The capture groups of a match found by either engine, as byte offsets
+/// into the searched text.
+pub enum Captures<'t> {
+    Auto(regex::Captures<'t>),
+    ConstantTime(MetaCaptures),
+}
+
+impl Captures<'_> {
+    /// The amount of groups in the pattern, including the whole match.
+    pub fn len(&self) -> usize {
+        match self {
+            Captures::Auto(caps) => caps.len(),
+            Captures::ConstantTime(caps) => caps.group_len(),
+        }
+    }
+
+    pub fn get(&self, i: usize) -> Option<(usize, usize)> {
+        match self {
+            Captures::Auto(caps) => caps.get(i).map(|m| (m.start(), m.end())),
+            Captures::ConstantTime(caps) => caps.get_group(i).map(|s| (s.start, s.end)),
+        }
+    }
+
+    pub fn name(&self, name: &str) -> Option<(usize, usize)> {
+        match self {
+            Captures::Auto(caps) => caps.name(name).map(|m| (m.start(), m.end())),
+            Captures::ConstantTime(caps) => caps.get_group_by_name(name).map(|s| (s.start, s.end)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SemanticsKind {
+    Unicode,
+    Ascii,
+}
+
+/// What the character classes of a pattern mean, passed as `semantics=`
+/// when compiling.
+///
+///     UNICODE - `\w`, `\d`, `\s` and `\b` follow Unicode, the default.
+///     ASCII - `\w`, `\d`, `\s` and `\b` only consider ASCII characters,
+///         like Python's `re.ASCII`, which is also faster to match.
+#[pyclass]
+#[derive(Clone)]
+pub struct Semantics {
+    kind: SemanticsKind,
+}
+
+py_enum!(Semantics, SemanticsKind, "semantics", {
+    Unicode => UNICODE,
+    Ascii => ASCII,
+});
+
+impl SemanticsKind {
+    /// Rewrites a pattern to follow these semantics.
+    pub fn apply(self, pattern: &str) -> Result<String, String> {
+        match self {
+            SemanticsKind::Unicode => Ok(pattern.to_string()),
+            SemanticsKind::Ascii => ascii(pattern),
+        }
+    }
+}
+
+/// Rewrites every Perl class and word boundary in the pattern to its ASCII
+/// only form, leaving everything else as written.
+///
+/// Classes become POSIX classes rather than being put in a `(?-u:...)`
+/// group, as negated classes like `(?-u:\W)` could match invalid UTF-8 and
+/// aren't allowed in string patterns.
+fn ascii(pattern: &str) -> Result<String, String> {
+    let ast = Parser::new().parse(pattern).map_err(|e| e.to_string())?;
+
+    let mut edits = Vec::new();
+    collect(&ast, pattern, &mut edits);
+    edits.sort_by_key(|(span, _)| span.start.offset);
+
+    let mut out = String::with_capacity(pattern.len());
+    let mut last = 0;
+    for (span, replacement) in edits {
+        out.push_str(&pattern[last..span.start.offset]);
+        out.push_str(&replacement);
+        last = span.end.offset;
+    }
+    out.push_str(&pattern[last..]);
+
+    Ok(out)
+}
+
+fn collect(ast: &Ast, pattern: &str, edits: &mut Vec<(Span, String)>) {
+    match ast {
+        Ast::ClassPerl(class) => edits.push((class.span, format!("[{}]", posix_class(class)))),
+        Ast::ClassBracketed(class) => collect_set(&class.kind, edits),
+        Ast::Assertion(assertion) => match assertion.kind {
+            AssertionKind::StartLine
+            | AssertionKind::EndLine
+            | AssertionKind::StartText
+            | AssertionKind::EndText => {},
+            _ => {
+                let span = assertion.span;
+                let text = &pattern[span.start.offset..span.end.offset];
+                edits.push((span, format!("(?-u:{})", text)));
+            },
+        },
+        Ast::Repetition(rep) => collect(&rep.ast, pattern, edits),
+        Ast::Group(group) => collect(&group.ast, pattern, edits),
+        Ast::Concat(concat) => {
+            for ast in concat.asts.iter() {
+                collect(ast, pattern, edits);
+            }
+        },
+        Ast::Alternation(alt) => {
+            for ast in alt.asts.iter() {
+                collect(ast, pattern, edits);
+            }
+        },
+        _ => {},
+    }
+}
+
+fn collect_set(set: &ClassSet, edits: &mut Vec<(Span, String)>) {
+    match set {
+        ClassSet::Item(item) => collect_item(item, edits),
+        ClassSet::BinaryOp(op) => {
+            collect_set(&op.lhs, edits);
+            collect_set(&op.rhs, edits);
+        },
+    }
+}
+
+fn collect_item(item: &ClassSetItem, edits: &mut Vec<(Span, String)>) {
+    match item {
+        ClassSetItem::Perl(class) => edits.push((class.span, posix_class(class).to_string())),
+        ClassSetItem::Bracketed(class) => collect_set(&class.kind, edits),
+        ClassSetItem::Union(union) => {
+            for item in union.items.iter() {
+                collect_item(item, edits);
+            }
+        },
+        _ => {},
+    }
+}
+
+/// The POSIX class matching the ASCII form of a Perl class, as it is written
+/// inside a bracketed class.
+fn posix_class(class: &ClassPerl) -> &'static str {
+    match (&class.kind, class.negated) {
+        (ClassPerlKind::Digit, false) => "[:digit:]",
+        (ClassPerlKind::Digit, true) => "[:^digit:]",
+        (ClassPerlKind::Space, false) => "[:space:]",
+        (ClassPerlKind::Space, true) => "[:^space:]",
+        (ClassPerlKind::Word, false) => "[:word:]",
+        (ClassPerlKind::Word, true) => "[:^word:]",
+    }
+}
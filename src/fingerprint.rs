@@ -0,0 +1,77 @@
+/// Bumped whenever the fingerprint input changes so old and new
+/// fingerprints of the same pattern never collide by accident.
+const VERSION: &str = "regex-fingerprint-v1";
+
+const FNV_OFFSET: u128 = 0x6c62272e07bb014262b821756295c58d;
+const FNV_PRIME: u128 = 0x0000000001000000000000000000013b;
+
+/// 128 bit FNV-1a, used rather than the std hasher as its output is
+/// guaranteed to stay the same across Rust versions and platforms.
+pub struct Fnv(u128);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Fnv(FNV_OFFSET)
+    }
+}
+
+impl Fnv {
+    pub fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u128;
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    /// Writes a length prefixed field so adjacent fields can't run into
+    /// each other.
+    pub fn field(&mut self, bytes: &[u8]) {
+        self.write(&(bytes.len() as u64).to_le_bytes());
+        self.write(bytes);
+    }
+
+    pub fn hex(&self) -> String {
+        format!("{:032x}", self.0)
+    }
+
+    /// Formats the hash as a version 8 (custom) UUID string.
+    pub fn uuid(&self) -> String {
+        let value = self.0 & !(0xf << 76) & !(0x3 << 62) | (0x8 << 76) | (0x2 << 62);
+        let hex = format!("{:032x}", value);
+        format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+    }
+}
+
+/// Computes the fingerprint of a pattern compiled with the given engine
+/// options.
+///
+/// The pattern is normalized by printing the HIR it translates to, so
+/// patterns that only differ in formatting (`[a-c]` and `[abc]`, verbose
+/// mode whitespace, redundant groups) share a fingerprint.
+pub fn fingerprint(pattern: &str, options: &[(&str, &str)]) -> Result<String, String> {
+    let hir = regex_syntax::parse(pattern).map_err(|e| e.to_string())?;
+
+    let mut hasher = Fnv::default();
+    hasher.field(VERSION.as_bytes());
+    hasher.field(hir.to_string().as_bytes());
+    for (name, value) in options {
+        hasher.field(name.as_bytes());
+        hasher.field(value.as_bytes());
+    }
+
+    Ok(hasher.hex())
+}
+
+/// Computes the ID of a match, made from the fingerprint of the pattern it
+/// was found by, the document it was found in and its span, so the same
+/// match gets the same ID wherever it is computed.
+pub fn match_id(fingerprint: &str, doc_id: &str, start: usize, end: usize) -> String {
+    let mut hasher = Fnv::default();
+    hasher.field(VERSION.as_bytes());
+    hasher.field(fingerprint.as_bytes());
+    hasher.field(doc_id.as_bytes());
+    hasher.write(&(start as u64).to_le_bytes());
+    hasher.write(&(end as u64).to_le_bytes());
+
+    hasher.uuid()
+}
@@ -0,0 +1,99 @@
+use regex_syntax::ast::{Ast, Flag, Flags, FlagsItemKind, GroupKind, Span};
+use regex_syntax::ast::parse::Parser;
+
+
+/// An inline flag directive and the part of the pattern it affects, spans
+/// are byte ranges of the pattern.
+pub struct FlagScope {
+    /// The span of the directive itself, e.g. `(?i)` or the `(?i:` of a
+    /// group.
+    pub directive: (usize, usize),
+
+    /// The span of the pattern the flags apply to.
+    pub region: (usize, usize),
+
+    /// Every flag the directive sets, and if it is enabled or disabled.
+    pub flags: Vec<(char, bool)>,
+}
+
+/// Finds every inline flag directive in the pattern along with the region
+/// it affects, in the order they appear.
+///
+/// A `(?flags)` directive applies from the end of the directive to the end
+/// of the group it is in (or the end of the pattern), including any later
+/// alternation branches, while a `(?flags:...)` group only applies within
+/// the group.
+pub fn analyze(pattern: &str) -> Result<Vec<FlagScope>, String> {
+    let ast = Parser::new().parse(pattern).map_err(|e| e.to_string())?;
+
+    let mut scopes = Vec::new();
+    walk(&ast, pattern.len(), &mut scopes);
+    scopes.sort_by_key(|scope| scope.directive);
+
+    Ok(scopes)
+}
+
+fn walk(ast: &Ast, scope_end: usize, scopes: &mut Vec<FlagScope>) {
+    match ast {
+        Ast::Flags(set) => scopes.push(FlagScope {
+            directive: span(&set.span),
+            region: (set.span.end.offset, scope_end),
+            flags: flag_list(&set.flags),
+        }),
+        Ast::Group(group) => {
+            // The content of a group ends right before its closing `)`.
+            let content_end = group.span.end.offset - 1;
+            if let GroupKind::NonCapturing(flags) = &group.kind {
+                if !flags.items.is_empty() {
+                    scopes.push(FlagScope {
+                        directive: (group.span.start.offset, flags.span.end.offset + 1),
+                        region: (flags.span.end.offset + 1, content_end),
+                        flags: flag_list(flags),
+                    });
+                }
+            }
+            walk(&group.ast, content_end, scopes);
+        },
+        Ast::Repetition(rep) => walk(&rep.ast, scope_end, scopes),
+        Ast::Concat(concat) => {
+            for ast in concat.asts.iter() {
+                walk(ast, scope_end, scopes);
+            }
+        },
+        Ast::Alternation(alt) => {
+            for ast in alt.asts.iter() {
+                walk(ast, scope_end, scopes);
+            }
+        },
+        _ => {},
+    }
+}
+
+fn span(span: &Span) -> (usize, usize) {
+    (span.start.offset, span.end.offset)
+}
+
+fn flag_list(flags: &Flags) -> Vec<(char, bool)> {
+    let mut enabled = true;
+    let mut list = Vec::new();
+    for item in flags.items.iter() {
+        match item.kind {
+            FlagsItemKind::Negation => enabled = false,
+            FlagsItemKind::Flag(flag) => list.push((flag_char(flag), enabled)),
+        }
+    }
+
+    list
+}
+
+fn flag_char(flag: Flag) -> char {
+    match flag {
+        Flag::CaseInsensitive => 'i',
+        Flag::MultiLine => 'm',
+        Flag::DotMatchesNewLine => 's',
+        Flag::SwapGreed => 'U',
+        Flag::Unicode => 'u',
+        Flag::CRLF => 'R',
+        Flag::IgnoreWhitespace => 'x',
+    }
+}
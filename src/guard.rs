@@ -0,0 +1,59 @@
+use pyo3::prelude::*;
+use pyo3::create_exception;
+use pyo3::exceptions::PyRuntimeError;
+
+use parking_lot::{const_mutex, Mutex, MutexGuard};
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+
+create_exception!(regex, LockTimeout, PyRuntimeError);
+
+/// How long to wait for a lock before giving up, in milliseconds.
+static TIMEOUT_MS: AtomicU64 = AtomicU64::new(5000);
+
+pub fn timeout() -> Duration {
+    Duration::from_millis(TIMEOUT_MS.load(Ordering::Relaxed))
+}
+
+pub fn set_timeout(timeout: Duration) {
+    TIMEOUT_MS.store(timeout.as_millis() as u64, Ordering::Relaxed);
+}
+
+/// State that can be changed from Python, guarded by its own lock rather
+/// than by the GIL.
+///
+/// The GIL is released by `allow_threads` sections and won't exist at all
+/// in free-threaded Python, so anything mutable has to be safe on its own.
+/// Waiting for the lock gives up with `LockTimeout` after the configured
+/// timeout instead of blocking forever, so a thread holding the lock while
+/// waiting for the GIL shows up as an error rather than a hang.
+pub struct Guarded<T> {
+    name: &'static str,
+    mutex: Mutex<T>,
+}
+
+impl<T> Guarded<T> {
+    pub const fn new(name: &'static str, value: T) -> Self {
+        Guarded { name, mutex: const_mutex(value) }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Whether some thread is currently holding the lock.
+    pub fn is_locked(&self) -> bool {
+        self.mutex.is_locked()
+    }
+
+    pub fn lock(&self) -> PyResult<MutexGuard<'_, T>> {
+        let timeout = timeout();
+        self.mutex.try_lock_for(timeout).ok_or_else(|| LockTimeout::new_err(format!(
+            "timed out after {}ms waiting for the {} lock",
+            timeout.as_millis(),
+            self.name,
+        )))
+    }
+}
@@ -1,11 +1,45 @@
 use pyo3::prelude::*;
 use pyo3::wrap_pyfunction;
-use pyo3::exceptions::PyValueError;
+use pyo3::exceptions::{PyIOError, PyTypeError, PyValueError};
+use pyo3::types::{PyDict, PyList, PyLong, PyString};
 
 use regex::{Regex, RegexSet};
+use regex_automata::{meta, Input};
 
 use mimalloc::MiMalloc;
 
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+mod cache;
+mod cidr;
+mod compare;
+mod coverage;
+mod encoding;
+mod engine;
+mod fingerprint;
+mod flags;
+mod guard;
+mod limits;
+mod macros;
+mod metrics;
+mod path_index;
+mod policy;
+mod preprocess;
+mod profile;
+mod schema;
+mod stream;
+mod summary;
+mod template;
+
+use encoding::Encoding;
+use engine::{Captures, Engine, EngineKind, Semantics, SemanticsKind};
+use limits::{Budget, CancellationToken, Limits};
+use preprocess::{Prepared, Transform};
+use summary::ScanSummary;
+use template::{FormatTemplate, Groups, Template};
+
 
 /// Faster memory allocator in Pyo3 context
 #[global_allocator]
@@ -14,16 +48,90 @@ static GLOBAL: MiMalloc = MiMalloc;
 /// Compiles and produces a regex class for matching strings to the regex
 /// pattern, it is recommended to use this over the function methods as
 /// compiling takes a while and shouldn't be constantly remade hurting performance.
+///
+/// Passing `engine=Engine.CONSTANT_TIME` disables the literal prefilter
+/// optimizations and makes `is_match`, `is_match_at` and `find` scan the
+/// whole string instead of exiting at the first match, this reduces how much
+/// the matching time leaks about partially matching input when scanning for
+/// secrets in attacker supplied strings at the cost of speed. The older
+/// `constant_time=True` does the same.
+///
+/// Passing `semantics=Semantics.ASCII` makes `\w`, `\d`, `\s` and `\b` only
+/// consider ASCII characters like Python's `re.ASCII`.
+///
+/// Passing `pre` with a list of transform names runs every string through
+/// those transforms (in order) before it is matched, all offsets and matched
+/// text returned still refer to the original string. The transforms are:
+///
+///     strip_ansi - Removes ANSI escape sequences like terminal colours.
+///     strip_control - Removes control characters other than whitespace.
+///     collapse_ws - Collapses every run of whitespace into a single space.
+///     lowercase - Lowercases every character.
 #[pyclass(name=Regex)]
 pub struct PyRegex {
     regex: Regex,
+
+    /// Prefilter free engine used for every search instead of `regex` when
+    /// constant time matching is enabled.
+    hardened: Option<meta::Regex>,
+
+    /// Transforms applied to every string before it is matched.
+    pre: Vec<Transform>,
 }
 
 #[pymethods]
 impl PyRegex {
     #[new]
-    fn new(pattern: &str) -> Self {
-        PyRegex { regex: Regex::new(pattern).unwrap() }
+    #[args(constant_time = "false", pre = "None", engine = "None", semantics = "None")]
+    fn new(
+        py: Python,
+        pattern: &str,
+        constant_time: bool,
+        pre: Option<Vec<&str>>,
+        engine: Option<&PyAny>,
+        semantics: Option<&PyAny>,
+    ) -> PyResult<Self> {
+        let engine = match (Engine::extract(engine)?, constant_time) {
+            (None, false) => EngineKind::Auto,
+            (None, true) | (Some(EngineKind::ConstantTime), _) => EngineKind::ConstantTime,
+            (Some(EngineKind::Auto), true) => {
+                return Err(PyValueError::new_err("constant_time=True conflicts with engine=Engine.AUTO"))
+            },
+            (Some(engine), false) => engine,
+        };
+        let semantics = Semantics::extract(semantics)?.unwrap_or(SemanticsKind::Unicode);
+
+        let pre = pre
+            .unwrap_or_default()
+            .into_iter()
+            .map(|name| Transform::from_name(name).ok_or_else(|| {
+                let names: Vec<&str> = Transform::ALL.iter().map(|t| t.name()).collect();
+                PyValueError::new_err(format!(
+                    "unknown transform {:?}, expected one of {}", name, names.join(", "),
+                ))
+            }))
+            .collect::<PyResult<Vec<Transform>>>()?;
+
+        let pattern = expand_pattern(py, pattern, semantics)?;
+        let regex = match Regex::new(&pattern) {
+            Ok(r) => r,
+            Err(e) => return Err(compile_error(e))
+        };
+
+        let hardened = if engine == EngineKind::ConstantTime {
+            let hardened = meta::Regex::builder()
+                .configure(meta::Regex::config().auto_prefilter(false))
+                .build(&pattern);
+
+            match hardened {
+                Ok(r) => Some(r),
+                Err(e) => return Err(compile_error(e))
+            }
+        } else {
+            None
+        };
+
+        Ok(PyRegex { regex, hardened, pre })
     }
 
     /// Matches the compiled regex string to another string passed to this
@@ -32,11 +140,31 @@ impl PyRegex {
     /// Args:
     ///     other:
     ///         The other string to be matched against the compiled regex
+    ///     budget:
+    ///         An optional Budget this call takes its time and bytes from.
+    ///     token:
+    ///         An optional CancellationToken that can cancel this call.
     ///
     /// Returns:
     ///     A bool signifying if it is a match or not.
-    fn is_match(&self, other: &str) -> bool {
-        self.regex.is_match(other)
+    #[args(budget = "None", token = "None")]
+    fn is_match(
+        &self,
+        other: &str,
+        budget: Option<PyRef<Budget>>,
+        token: Option<PyRef<CancellationToken>>,
+    ) -> PyResult<bool> {
+        let limits = Limits::new(budget.as_deref(), token.as_deref());
+        limits.start(other.len())?;
+        let prepared = Prepared::new(other, &self.pre);
+
+        let matched = match &self.hardened {
+            Some(hardened) => hardened_find(hardened, &prepared.text, 0).is_some(),
+            None => self.regex.is_match(&prepared.text),
+        };
+
+        metrics::MATCHES.add(matched as usize);
+        Ok(matched)
     }
 
     /// Matches the compiled regex string to another string passed to this
@@ -47,11 +175,33 @@ impl PyRegex {
     ///         The other string to be matched against the compiled regex.
     ///     start:
     ///         The starting index of the string you want to match against.
+    ///     budget:
+    ///         An optional Budget this call takes its time and bytes from.
+    ///     token:
+    ///         An optional CancellationToken that can cancel this call.
     ///
     /// Returns:
     ///     A bool signifying if it is a match or not.
-    fn is_match_at(&self, other: &str, start: usize) -> bool {
-        self.regex.is_match_at(other, start)
+    #[args(budget = "None", token = "None")]
+    fn is_match_at(
+        &self,
+        other: &str,
+        start: usize,
+        budget: Option<PyRef<Budget>>,
+        token: Option<PyRef<CancellationToken>>,
+    ) -> PyResult<bool> {
+        let limits = Limits::new(budget.as_deref(), token.as_deref());
+        limits.start(other.len())?;
+        let prepared = Prepared::new(other, &self.pre);
+        let start = prepared.transformed(start);
+
+        let matched = match &self.hardened {
+            Some(hardened) => hardened_find(hardened, &prepared.text, start).is_some(),
+            None => self.regex.is_match_at(&prepared.text, start),
+        };
+
+        metrics::MATCHES.add(matched as usize);
+        Ok(matched)
     }
 
     /// Matches the compiled regex string to another string passed to this
@@ -61,16 +211,36 @@ impl PyRegex {
     /// Args:
     ///     other:
     ///         The other string to be matched against the compiled regex.
+    ///     budget:
+    ///         An optional Budget this call takes its time and bytes from.
+    ///     token:
+    ///         An optional CancellationToken that can cancel this call.
     ///
     /// Returns:
     ///     Optional[str] - This can either be the matched text or None.
-    fn find(&self, other: &str) -> Option<String> {
-        let matched = match self.regex.find(other) {
+    #[args(budget = "None", token = "None")]
+    fn find(
+        &self,
+        other: &str,
+        budget: Option<PyRef<Budget>>,
+        token: Option<PyRef<CancellationToken>>,
+    ) -> PyResult<Option<String>> {
+        let limits = Limits::new(budget.as_deref(), token.as_deref());
+        limits.start(other.len())?;
+        let prepared = Prepared::new(other, &self.pre);
+
+        let matched = match &self.hardened {
+            Some(hardened) => hardened_find(hardened, &prepared.text, 0).map(|m| (m.start(), m.end())),
+            None => self.regex.find(&prepared.text).map(|m| (m.start(), m.end())),
+        };
+
+        let (start, end) = match matched {
             Some(m) => m,
-            _ => return None,
+            _ => return Ok(None),
         };
 
-        Some(matched.as_str().to_string())
+        metrics::MATCHES.inc();
+        Ok(Some(prepared.original_str(start, end).to_string()))
     }
 
     /// Matches the compiled regex string to another string passed to this
@@ -80,18 +250,48 @@ impl PyRegex {
     /// Args:
     ///     other:
     ///         The other string to be matched against the compiled regex.
+    ///     budget:
+    ///         An optional Budget this call takes its time and bytes from.
+    ///     token:
+    ///         An optional CancellationToken that can cancel this call.
+    ///     summary:
+    ///         Pass an amount of examples to return a ScanSummary keeping
+    ///         only that many of the first matches instead of every match.
     ///
     /// Returns:
-    ///     A list with n amount of text objects containing matched strings.
-    fn findall(&self, other: &str) -> Vec<String> {
-        let matched: Vec<String> = self.regex
-            .find_iter(other)
-            .map(|match_| {
-                match_.as_str().to_string()
-            })
-            .collect();
+    ///     A list with n amount of text objects containing matched strings,
+    ///     or a ScanSummary when `summary` is given.
+    #[args(budget = "None", token = "None", summary = "None")]
+    fn findall(
+        &self,
+        py: Python,
+        other: &str,
+        budget: Option<PyRef<Budget>>,
+        token: Option<PyRef<CancellationToken>>,
+        summary: Option<usize>,
+    ) -> PyResult<PyObject> {
+        let limits = Limits::new(budget.as_deref(), token.as_deref());
 
-        matched
+        if let Some(limit) = summary {
+            let matches = py.allow_threads(|| self.spans(other, &limits))?;
+            return Ok(summarize(other, &matches, &matches, limit).into_py(py));
+        }
+
+        limits.start(other.len())?;
+        let matched = py.allow_threads(|| -> PyResult<_> {
+            let prepared = Prepared::new(other, &self.pre);
+
+            let mut matched = Vec::new();
+            for (start, end) in self.find_iter(&prepared) {
+                limits.check()?;
+                matched.push(prepared.original_str(start, end).to_string());
+            }
+
+            Ok(matched)
+        })?;
+
+        metrics::MATCHES.add(matched.len());
+        Ok(matched.into_py(py))
     }
 
     /// Matches the compiled regex string to another string passed to this
@@ -101,17 +301,40 @@ impl PyRegex {
     /// Args:
     ///     other:
     ///         The other string to be matched against the compiled regex.
+    ///     budget:
+    ///         An optional Budget this call takes its time and bytes from.
+    ///     token:
+    ///         An optional CancellationToken that can cancel this call.
     ///
     /// Returns:
     ///     A list with n amount of lists containing grouped matches relating
     ///     to the compiled regex.
-    fn all_captures(&self, other: &str) -> Vec<Vec<Option<String>>> {
-        let mut caps = Vec::new();
-        for capture in self.regex.captures_iter(other) {
-            let new = list_captures(capture);
-            caps.push(new);
-        }
-        caps
+    #[args(budget = "None", token = "None")]
+    fn all_captures(
+        &self,
+        py: Python,
+        other: &str,
+        budget: Option<PyRef<Budget>>,
+        token: Option<PyRef<CancellationToken>>,
+    ) -> PyResult<Vec<Vec<Option<String>>>> {
+        let limits = Limits::new(budget.as_deref(), token.as_deref());
+        limits.start(other.len())?;
+
+        let caps = py.allow_threads(|| -> PyResult<_> {
+            let prepared = Prepared::new(other, &self.pre);
+
+            let mut caps = Vec::new();
+            for capture in self.captures_iter(&prepared) {
+                limits.check()?;
+                let new = list_captures(&prepared.captures(&capture));
+                caps.push(new);
+            }
+
+            Ok(caps)
+        })?;
+
+        metrics::MATCHES.add(caps.len());
+        Ok(caps)
     }
 
     /// Matches the compiled regex string to another string passed to this
@@ -121,34 +344,806 @@ impl PyRegex {
     /// Args:
     ///     other:
     ///         The other string to be matched against the compiled regex.
+    ///     budget:
+    ///         An optional Budget this call takes its time and bytes from.
+    ///     token:
+    ///         An optional CancellationToken that can cancel this call.
     ///
     /// Returns:
     ///     A list with containing grouped matches relating
     ///     to the compiled regex.
-    fn captures(&self, other: &str) -> Option<Vec<Option<String>>> {
-        let capture  = match self.regex.captures(other) {
-            Some(c) => c,
-            _ => return None,
-        };
-        let new = list_captures(capture);
+    #[args(budget = "None", token = "None")]
+    fn captures(
+        &self,
+        py: Python,
+        other: &str,
+        budget: Option<PyRef<Budget>>,
+        token: Option<PyRef<CancellationToken>>,
+    ) -> PyResult<Option<Vec<Option<String>>>> {
+        let limits = Limits::new(budget.as_deref(), token.as_deref());
+        limits.start(other.len())?;
+
+        let new = py.allow_threads(|| {
+            let prepared = Prepared::new(other, &self.pre);
+            self.first_captures(&prepared)
+                .map(|capture| list_captures(&prepared.captures(&capture)))
+        });
 
-        Some(new)
+        if new.is_some() {
+            metrics::MATCHES.inc();
+        }
+        Ok(new)
     }
 
     /// Function that given returns a vector of tuples that contain
     /// (start_match, end_match+1) according to the compiled regex.
+    ///
+    /// Passing a `doc_id` adds a deterministic ID to every match, made from
+    /// the regex fingerprint, the `doc_id` and the span of the match, so the
+    /// same match can be correlated between processing stages without
+    /// hashing it again in Python.
+    ///
+    /// Args:
+    ///     other:
+    ///         The other string to be matched against the compiled regex.
+    ///     budget:
+    ///         An optional Budget this call takes its time and bytes from.
+    ///     token:
+    ///         An optional CancellationToken that can cancel this call.
+    ///     doc_id:
+    ///         An optional ID of the document the string came from.
+    ///     summary:
+    ///         Pass an amount of examples to return a ScanSummary keeping
+    ///         only that many of the first matches instead of every match.
+    ///
+    /// Returns:
+    ///     A vector of tuples that contain (start_match, end_match+1), or
+    ///     (start_match, end_match+1, id) with the ID as a UUID string when
+    ///     `doc_id` is given, or a ScanSummary when `summary` is given.
+    #[args(budget = "None", token = "None", doc_id = "None", summary = "None")]
+    fn matches(
+        &self,
+        py: Python,
+        other: &str,
+        budget: Option<PyRef<Budget>>,
+        token: Option<PyRef<CancellationToken>>,
+        doc_id: Option<&str>,
+        summary: Option<usize>,
+    ) -> PyResult<PyObject> {
+        if doc_id.is_some() && summary.is_some() {
+            return Err(PyValueError::new_err("doc_id and summary can't be used together"))
+        }
+
+        let limits = Limits::new(budget.as_deref(), token.as_deref());
+        let matches = py.allow_threads(|| self.spans(other, &limits))?;
+
+        if let Some(limit) = summary {
+            return Ok(summarize(other, &matches, &matches, limit).into_py(py));
+        }
+
+        let doc_id = match doc_id {
+            Some(doc_id) => doc_id,
+            None => return Ok(matches.into_py(py)),
+        };
+
+        let fingerprint = self.fingerprint()?;
+        let matches: Vec<(usize, usize, String)> = matches
+            .into_iter()
+            .map(|(start, end)| (start, end, fingerprint::match_id(&fingerprint, doc_id, start, end)))
+            .collect();
+
+        Ok(matches.into_py(py))
+    }
+
+    /// Runs a second pattern only inside the text captured by a group of
+    /// every match of the compiled regex, returning the spans it matched as
+    /// offsets into the whole string rather than into the group.
+    ///
+    /// Args:
+    ///     other:
+    ///         The other string to be matched against the compiled regex.
+    ///     group:
+    ///         The name or index of the group the second pattern runs in.
+    ///     inner_pattern:
+    ///         The second pattern, either a compiled Regex or a pattern
+    ///         string which is compiled and cached like `regex.matches`
+    ///         with the default engine.
+    ///     budget:
+    ///         An optional Budget this call takes its time and bytes from.
+    ///     token:
+    ///         An optional CancellationToken that can cancel this call.
+    ///
+    /// Returns:
+    ///     A vector of tuples that contain (start_match, end_match+1) of the
+    ///     second pattern in the whole string, matches of the compiled regex
+    ///     where the group didn't take part are skipped.
+    #[args(budget = "None", token = "None")]
+    fn match_within_group(
+        &self,
+        py: Python,
+        other: &str,
+        group: &PyAny,
+        inner_pattern: &PyAny,
+        budget: Option<PyRef<Budget>>,
+        token: Option<PyRef<CancellationToken>>,
+    ) -> PyResult<Vec<(usize, usize)>> {
+        let group = self.group_index(group)?;
+        let limits = Limits::new(budget.as_deref(), token.as_deref());
+
+        let compiled = inner_pattern.extract::<PyRef<PyRegex>>();
+        let inner_pattern = match &compiled {
+            Ok(compiled) => Inner::Compiled(compiled),
+            Err(_) => Inner::Cached(cache::get_or_compile(py, inner_pattern.extract()?)?),
+        };
+
+        limits.start(other.len())?;
+        py.allow_threads(|| {
+            let prepared = Prepared::new(other, &self.pre);
+
+            let mut matches = Vec::new();
+            for capture in self.captures_iter(&prepared) {
+                limits.check()?;
+                let (start, end) = match capture.get(group) {
+                    Some((start, end)) => prepared.original(start, end),
+                    None => continue,
+                };
+
+                let inner = match &inner_pattern {
+                    Inner::Compiled(compiled) => compiled.spans(&other[start..end], &limits)?,
+                    Inner::Cached(cached) => {
+                        limits.start(end - start)?;
+                        let spans: Vec<(usize, usize)> = cached
+                            .find_iter(&other[start..end])
+                            .map(|m| (m.start(), m.end()))
+                            .collect();
+                        metrics::MATCHES.add(spans.len());
+                        spans
+                    },
+                };
+
+                matches.extend(inner.into_iter().map(|(s, e)| (start + s, start + e)));
+            }
+
+            Ok(matches)
+        })
+    }
+
+    /// Matches the compiled regex against a sequence of code points, such as
+    /// a list of ints, an `array('I')` or an `array('u')`, instead of a
+    /// string.
+    ///
+    /// Args:
+    ///     codepoints:
+    ///         An iterable of ints or single character strings.
+    ///     budget:
+    ///         An optional Budget this call takes its time and bytes from.
+    ///     token:
+    ///         An optional CancellationToken that can cancel this call.
+    ///
+    /// Returns:
+    ///     A bool signifying if it is a match or not.
+    #[args(budget = "None", token = "None")]
+    fn is_match_codepoints(
+        &self,
+        codepoints: &PyAny,
+        budget: Option<PyRef<Budget>>,
+        token: Option<PyRef<CancellationToken>>,
+    ) -> PyResult<bool> {
+        let text = codepoints_to_string(codepoints)?;
+        self.is_match(&text, budget, token)
+    }
+
+    /// Matches the compiled regex against a sequence of code points, such as
+    /// a list of ints, an `array('I')` or an `array('u')`, returning the
+    /// positions of all matches as indexes into the sequence.
+    ///
+    /// Args:
+    ///     codepoints:
+    ///         An iterable of ints or single character strings.
+    ///     budget:
+    ///         An optional Budget this call takes its time and bytes from.
+    ///     token:
+    ///         An optional CancellationToken that can cancel this call.
+    ///
+    /// Returns:
+    ///     A vector of tuples that contain (start_match, end_match+1) in code
+    ///     point indexes.
+    #[args(budget = "None", token = "None")]
+    fn matches_codepoints(
+        &self,
+        py: Python,
+        codepoints: &PyAny,
+        budget: Option<PyRef<Budget>>,
+        token: Option<PyRef<CancellationToken>>,
+    ) -> PyResult<Vec<(usize, usize)>> {
+        let text = codepoints_to_string(codepoints)?;
+        let limits = Limits::new(budget.as_deref(), token.as_deref());
+
+        py.allow_threads(|| Ok(char_spans(&text, self.spans(&text, &limits)?)))
+    }
+
+    /// Reads and decodes a whole file, then finds the position of every
+    /// match of the compiled regex in the decoded text. UTF-16 files, such
+    /// as those commonly written by Windows, are decoded natively instead of
+    /// needing a slow decode in Python first.
+    ///
+    /// Invalid sequences are replaced with U+FFFD before matching, and a
+    /// byte order mark found by "auto" isn't part of the decoded text.
+    ///
+    /// Args:
+    ///     path:
+    ///         The path of the file to search.
+    ///     encoding:
+    ///         One of "utf-8", "utf-16-le" or "utf-16-be", or "auto" to
+    ///         detect UTF-16 by its byte order mark or NUL bytes and fall
+    ///         back to UTF-8.
+    ///     budget:
+    ///         An optional Budget this call takes its time and bytes from.
+    ///     token:
+    ///         An optional CancellationToken that can cancel this call.
+    ///     summary:
+    ///         Pass an amount of examples to return a ScanSummary keeping
+    ///         only that many of the first matches instead of every match.
+    ///
+    /// Returns:
+    ///     A vector of tuples that contain (start_match, end_match+1) in
+    ///     characters of the decoded text, the same as indexing the string
+    ///     Python would decode the file to, or a ScanSummary when `summary`
+    ///     is given.
+    #[args(encoding = "\"auto\"", budget = "None", token = "None", summary = "None")]
+    fn search_file(
+        &self,
+        py: Python,
+        path: &str,
+        encoding: &str,
+        budget: Option<PyRef<Budget>>,
+        token: Option<PyRef<CancellationToken>>,
+        summary: Option<usize>,
+    ) -> PyResult<PyObject> {
+        let encoding = match encoding {
+            "auto" => None,
+            name => Some(Encoding::from_name(name).ok_or_else(|| {
+                let names: Vec<&str> = Encoding::ALL.iter().map(|e| e.name()).collect();
+                PyValueError::new_err(format!(
+                    "unknown encoding {:?}, expected auto or one of {}", name, names.join(", "),
+                ))
+            })?),
+        };
+
+        let bytes = std::fs::read(path).map_err(|e| PyIOError::new_err(e.to_string()))?;
+        let limits = Limits::new(budget.as_deref(), token.as_deref());
+
+        let (text, matches, chars) = py.allow_threads(|| -> PyResult<_> {
+            let (encoding, bom) = match encoding {
+                Some(encoding) => (encoding, 0),
+                None => Encoding::detect(&bytes),
+            };
+
+            let text = encoding.decode(&bytes[bom..]);
+            let matches = self.spans(&text, &limits)?;
+            let chars = char_spans(&text, matches.clone());
+            Ok((text, matches, chars))
+        })?;
+
+        match summary {
+            Some(limit) => Ok(summarize(&text, &matches, &chars, limit).into_py(py)),
+            None => Ok(chars.into_py(py)),
+        }
+    }
+
+    /// Replaces the leftmost-first match of the compiled regex in the string
+    /// passed to this function with the replacement template.
+    ///
+    /// The template can reference capture groups with `$1`, `$name` or
+    /// `${name}` (`$$` for a literal dollar), and `\U` / `\L` convert all
+    /// following text to upper / lower case until a `\E` is reached.
+    ///
+    /// Args:
+    ///     other:
+    ///         The other string to be matched against the compiled regex.
+    ///     rep:
+    ///         The replacement template.
+    ///     budget:
+    ///         An optional Budget this call takes its time and bytes from.
+    ///     token:
+    ///         An optional CancellationToken that can cancel this call.
+    ///
+    /// Returns:
+    ///     The string with the replacement applied.
+    #[args(budget = "None", token = "None")]
+    fn replace(
+        &self,
+        py: Python,
+        other: &str,
+        rep: &str,
+        budget: Option<PyRef<Budget>>,
+        token: Option<PyRef<CancellationToken>>,
+    ) -> PyResult<String> {
+        self.replacen(py, other, 1, rep, budget, token)
+    }
+
+    /// Replaces all non-overlapping matches of the compiled regex in the
+    /// string passed to this function with the replacement template.
+    ///
+    /// See `replace` for the template syntax.
+    ///
+    /// Args:
+    ///     other:
+    ///         The other string to be matched against the compiled regex.
+    ///     rep:
+    ///         The replacement template.
+    ///     budget:
+    ///         An optional Budget this call takes its time and bytes from.
+    ///     token:
+    ///         An optional CancellationToken that can cancel this call.
+    ///
+    /// Returns:
+    ///     The string with the replacements applied.
+    #[args(budget = "None", token = "None")]
+    fn replace_all(
+        &self,
+        py: Python,
+        other: &str,
+        rep: &str,
+        budget: Option<PyRef<Budget>>,
+        token: Option<PyRef<CancellationToken>>,
+    ) -> PyResult<String> {
+        self.replacen(py, other, 0, rep, budget, token)
+    }
+
+    /// Replaces at most `limit` non-overlapping matches of the compiled regex
+    /// in the string passed to this function with the replacement template,
+    /// a limit of 0 replaces all matches.
+    ///
+    /// See `replace` for the template syntax.
+    ///
     /// Args:
     ///     other:
     ///         The other string to be matched against the compiled regex.
+    ///     limit:
+    ///         The maximum amount of replacements to make.
+    ///     rep:
+    ///         The replacement template.
+    ///     budget:
+    ///         An optional Budget this call takes its time and bytes from.
+    ///     token:
+    ///         An optional CancellationToken that can cancel this call.
     ///
     /// Returns:
-    ///     A vector of tuples that contain (start_match, end_match+1).
-    fn matches(&self, other: &str) -> Vec<(usize, usize)> {
+    ///     The string with the replacements applied.
+    #[args(budget = "None", token = "None")]
+    fn replacen(
+        &self,
+        py: Python,
+        other: &str,
+        limit: usize,
+        rep: &str,
+        budget: Option<PyRef<Budget>>,
+        token: Option<PyRef<CancellationToken>>,
+    ) -> PyResult<String> {
+        let limits = Limits::new(budget.as_deref(), token.as_deref());
+        limits.start(other.len())?;
+
+        let template = Template::parse(rep);
+        let (replaced, count) = py.allow_threads(|| -> PyResult<_> {
+            let prepared = Prepared::new(other, &self.pre);
+
+            // Replacements are made on the original string, only the matching
+            // is done against the transformed text.
+            let mut replaced = String::with_capacity(other.len());
+            let mut last = 0;
+            let mut count = 0;
+            for capture in self.captures_iter(&prepared) {
+                limits.check()?;
+                if limit > 0 && count >= limit {
+                    // The hardened engine keeps scanning past the last
+                    // replacement so the time taken doesn't depend on it.
+                    match self.hardened {
+                        Some(_) => continue,
+                        None => break,
+                    }
+                }
+
+                let (start, end) = capture.get(0).unwrap();
+                let (start, end) = prepared.original(start, end);
+                if start < last {
+                    continue;
+                }
+                count += 1;
+
+                replaced.push_str(&other[last..start]);
+                template.expand(&prepared.captures(&capture), &mut replaced);
+                last = end;
+            }
+            replaced.push_str(&other[last..]);
+
+            Ok((replaced, count))
+        })?;
+
+        metrics::MATCHES.add(count);
+        Ok(replaced)
+    }
+
+    /// Checks a replacement template against the compiled regex without
+    /// using it, so templates can be validated before they are saved.
+    ///
+    /// See `replace` for the template syntax.
+    ///
+    /// Args:
+    ///     template:
+    ///         The replacement template to check.
+    ///
+    /// Returns:
+    ///     A list of problems ordered by position, each one a dict with the
+    ///     `kind` of problem (`unknown_group`, `ambiguous_reference` or
+    ///     `bad_syntax`), a human readable `message` and the `span`
+    ///     (start, end) of the problem within the template. The list is empty
+    ///     if the template has no problems.
+    fn validate_template(&self, py: Python, template: &str) -> PyResult<PyObject> {
+        let problems = Template::parse(template).validate(&self.regex, template);
+
+        let mut out = Vec::with_capacity(problems.len());
+        for problem in problems {
+            let (start, end) = problem.span;
+            let dict = PyDict::new(py);
+            dict.set_item("kind", problem.kind.name())?;
+            dict.set_item("message", problem.message)?;
+            dict.set_item("span", (char_offset(template, start), char_offset(template, end)))?;
+            out.push(dict);
+        }
+
+        Ok(PyList::new(py, out).into())
+    }
+
+    /// Renders every match of the compiled regex in the string passed to
+    /// this function through a format template and joins them together,
+    /// making it quick to produce delimited extracts of large inputs.
+    ///
+    /// The template uses `{1}` or `{name}` for capture groups, `{0}` for the
+    /// whole match and `{{` / `}}` for literal braces, e.g. `"{1}\t{name}\n"`
+    /// renders a TSV row per match. Groups that didn't take part in a match
+    /// render as an empty string.
+    ///
+    /// Args:
+    ///     other:
+    ///         The other string to be matched against the compiled regex.
+    ///     template:
+    ///         The format template each match is rendered with.
+    ///     budget:
+    ///         An optional Budget this call takes its time and bytes from.
+    ///     token:
+    ///         An optional CancellationToken that can cancel this call.
+    ///
+    /// Returns:
+    ///     A single string containing every rendered match.
+    #[args(budget = "None", token = "None")]
+    fn format_matches(
+        &self,
+        py: Python,
+        other: &str,
+        template: &str,
+        budget: Option<PyRef<Budget>>,
+        token: Option<PyRef<CancellationToken>>,
+    ) -> PyResult<String> {
+        let template = match FormatTemplate::compile(template, &self.regex) {
+            Ok(t) => t,
+            Err(e) => return Err(PyValueError::new_err(e)),
+        };
+
+        let limits = Limits::new(budget.as_deref(), token.as_deref());
+        limits.start(other.len())?;
+
+        let (out, count) = py.allow_threads(|| -> PyResult<_> {
+            let prepared = Prepared::new(other, &self.pre);
+
+            let mut out = String::new();
+            let mut count = 0;
+            for capture in self.captures_iter(&prepared) {
+                limits.check()?;
+                template.render(&prepared.captures(&capture), &mut out);
+                count += 1;
+            }
+
+            Ok((out, count))
+        })?;
+
+        metrics::MATCHES.add(count);
+        Ok(out)
+    }
+
+    /// Splits the string passed to this function into a stream of tokens
+    /// made of every match of the compiled regex and the gaps between them,
+    /// so joining the text of every token gives back the exact original
+    /// string. Empty matches don't produce a token, the stream always ends
+    /// with an empty "end" token at the length of the string so
+    /// `detokenize` can tell when tokens are missing from the end.
+    ///
+    /// Args:
+    ///     other:
+    ///         The other string to be matched against the compiled regex.
+    ///     budget:
+    ///         An optional Budget this call takes its time and bytes from.
+    ///     token:
+    ///         An optional CancellationToken that can cancel this call.
+    ///
+    /// Returns:
+    ///     A list of (kind, text, start, end) tuples in order, kind being
+    ///     either "match", "gap" or "end".
+    #[args(budget = "None", token = "None")]
+    fn tokenize<'a>(
+        &self,
+        py: Python,
+        other: &'a str,
+        budget: Option<PyRef<Budget>>,
+        token: Option<PyRef<CancellationToken>>,
+    ) -> PyResult<Vec<(&'static str, &'a str, usize, usize)>> {
+        let limits = Limits::new(budget.as_deref(), token.as_deref());
+        let spans = py.allow_threads(|| self.spans(other, &limits))?;
+
+        let mut tokens = Vec::new();
+        let mut last = 0;
+        for (start, end) in spans {
+            if start == end || start < last {
+                continue;
+            }
+            if last < start {
+                tokens.push(("gap", &other[last..start], last, start));
+            }
+            tokens.push(("match", &other[start..end], start, end));
+            last = end;
+        }
+        if last < other.len() {
+            tokens.push(("gap", &other[last..], last, other.len()));
+        }
+        tokens.push(("end", "", other.len(), other.len()));
+
+        Ok(tokens)
+    }
+
+    /// Counts every distinct matched string of the compiled regex across a
+    /// string or an iterable of strings, returning the most frequent ones.
+    ///
+    /// Args:
+    ///     texts:
+    ///         A string or an iterable of strings to be matched against the
+    ///         compiled regex.
+    ///     k:
+    ///         The amount of matched strings to return.
+    ///     token:
+    ///         An optional CancellationToken that can cancel this call.
+    ///
+    /// Returns:
+    ///     A list of up to `k` tuples of (matched_text, count), most frequent
+    ///     first with ties ordered by the matched text.
+    #[args(k = "10", token = "None")]
+    fn top_matches(
+        &self,
+        py: Python,
+        texts: &PyAny,
+        k: usize,
+        token: Option<PyRef<CancellationToken>>,
+    ) -> PyResult<Vec<(String, usize)>> {
+        let texts: Vec<&str> = if let Ok(text) = texts.downcast::<PyString>() {
+            vec![text.to_str()?]
+        } else {
+            texts
+                .iter()?
+                .map(|text| text?.extract::<&str>())
+                .collect::<PyResult<Vec<&str>>>()?
+        };
+
+        let limits = Limits::new(None, token.as_deref());
+        let top = py.allow_threads(|| -> PyResult<Vec<(&str, usize)>> {
+            let mut counts: HashMap<&str, usize> = HashMap::new();
+            for text in texts {
+                limits.start(text.len())?;
+                let prepared = Prepared::new(text, &self.pre);
+                for (start, end) in self.find_iter(&prepared) {
+                    limits.check()?;
+                    *counts.entry(prepared.original_str(start, end)).or_insert(0) += 1;
+                }
+            }
+
+            metrics::MATCHES.add(counts.values().sum());
+
+            let mut top: Vec<(&str, usize)> = counts.into_iter().collect();
+            top.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+            top.truncate(k);
+            Ok(top)
+        })?;
+
+        Ok(top.into_iter().map(|(text, count)| (text.to_string(), count)).collect())
+    }
+
+    /// Returns a stable fingerprint of the compiled regex, made from the
+    /// normalized pattern and the options it was compiled with.
+    ///
+    /// Patterns which only differ in formatting, like `[a-c]` and `[abc]`,
+    /// share a fingerprint making it a better cache or deduplication key
+    /// than the raw pattern string. The fingerprint stays the same between
+    /// processes and machines, though it may change between releases of
+    /// this library.
+    ///
+    /// Returns:
+    ///     A 32 character hex string.
+    fn fingerprint(&self) -> PyResult<String> {
+        let constant_time = if self.hardened.is_some() { "true" } else { "false" };
+        let pre: Vec<&str> = self.pre.iter().map(|t| t.name()).collect();
+        let pre = pre.join(",");
+        let options = [("constant_time", constant_time), ("pre", pre.as_str())];
+
+        match fingerprint::fingerprint(self.regex.as_str(), &options) {
+            Ok(f) => Ok(f),
+            Err(e) => Err(PyValueError::new_err(e)),
+        }
+    }
+
+    /// Matches the compiled regex against every string in the iterable and
+    /// reports which alternation branches and capture groups ever took part
+    /// in a match, useful for finding dead branches in complex patterns.
+    ///
+    /// Branches and groups inside a repetition are only counted for the last
+    /// repetition of each match.
+    ///
+    /// Args:
+    ///     texts:
+    ///         An iterable of strings to be matched against the compiled regex.
+    ///     token:
+    ///         An optional CancellationToken that can cancel this call.
+    ///
+    /// Returns:
+    ///     A dict containing the amount of `texts` and `matches` seen, a list
+    ///     of `branches` holding a dict with the `span` (start, end), the
+    ///     `pattern` text and the amount of matches it had `hits` in for every
+    ///     alternation branch, and a list of `groups` holding a dict with the
+    ///     `index`, `name` and `hits` of every capture group.
+    #[args(token = "None")]
+    fn coverage(&self, py: Python, texts: &PyAny, token: Option<PyRef<CancellationToken>>) -> PyResult<PyObject> {
+        // Coverage is found with an instrumented copy of the pattern on the
+        // default engine, which doesn't give any timing guarantees.
+        if self.hardened.is_some() {
+            return Err(PyValueError::new_err("coverage isn't supported with Engine.CONSTANT_TIME"));
+        }
+
+        let pattern = self.regex.as_str();
+        let instrumented = match coverage::Instrumented::new(pattern) {
+            Ok(i) => i,
+            Err(e) => return Err(PyValueError::new_err(e)),
+        };
+
+        let texts = texts
+            .iter()?
+            .map(|text| Ok(Prepared::new(text?.extract::<&str>()?, &self.pre).text))
+            .collect::<PyResult<Vec<_>>>()?;
+        let limits = Limits::new(None, token.as_deref());
+        let report = py.allow_threads(|| {
+            instrumented.run(texts.iter().map(|text| text.as_ref()), &limits)
+        })?;
+
+        metrics::MATCHES.add(report.matches);
+
+        let mut branches = Vec::with_capacity(report.branches.len());
+        for branch in report.branches {
+            let (start, end) = (branch.span.start.offset, branch.span.end.offset);
+            let dict = PyDict::new(py);
+            dict.set_item("span", (char_offset(pattern, start), char_offset(pattern, end)))?;
+            dict.set_item("pattern", &pattern[start..end])?;
+            dict.set_item("hits", branch.hits)?;
+            branches.push(dict);
+        }
+
+        let mut groups = Vec::with_capacity(report.groups.len());
+        for group in report.groups {
+            let dict = PyDict::new(py);
+            dict.set_item("index", group.index)?;
+            dict.set_item("name", group.name)?;
+            dict.set_item("hits", group.hits)?;
+            groups.push(dict);
+        }
+
+        let out = PyDict::new(py);
+        out.set_item("texts", report.texts)?;
+        out.set_item("matches", report.matches)?;
+        out.set_item("branches", PyList::new(py, branches))?;
+        out.set_item("groups", PyList::new(py, groups))?;
+
+        Ok(out.into())
+    }
+}
+
+/// The second pattern of `match_within_group`.
+enum Inner<'a> {
+    Compiled(&'a PyRegex),
+    Cached(std::sync::Arc<Regex>),
+}
+
+impl PyRegex {
+    /// Resolves a group given by name or index to its index.
+    fn group_index(&self, group: &PyAny) -> PyResult<usize> {
+        if group.downcast::<PyLong>().is_ok() {
+            // Negative and huge ints are out of range like any other index.
+            let index = group.extract::<isize>().ok().filter(|&i| i >= 0);
+            return match index {
+                Some(index) if (index as usize) < self.regex.captures_len() => Ok(index as usize),
+                _ => Err(PyValueError::new_err(format!(
+                    "group {} out of range, the pattern has {} groups",
+                    group,
+                    self.regex.captures_len() - 1,
+                ))),
+            };
+        }
+
+        let name: &str = group
+            .extract()
+            .map_err(|_| PyTypeError::new_err(format!(
+                "group must be a name or an index, not {}",
+                group.get_type().name(),
+            )))?;
+        let names: Vec<&str> = self.regex.capture_names().flatten().collect();
+        match self.regex.capture_names().position(|n| n == Some(name)) {
+            Some(index) => Ok(index),
+            None => Err(PyValueError::new_err(format!(
+                "unknown group {:?}, expected one of {}",
+                name,
+                names.join(", "),
+            ))),
+        }
+    }
+
+    /// Iterates over the (start, end) of every match in the transformed
+    /// text, with the hardened engine when constant time matching is enabled.
+    /// Matches that map back into the original characters of the previous
+    /// match are skipped.
+    fn find_iter<'a>(&'a self, prepared: &'a Prepared<'a>) -> Box<dyn Iterator<Item = (usize, usize)> + 'a> {
+        let text = &*prepared.text;
+        let matches: Box<dyn Iterator<Item = (usize, usize)>> = match &self.hardened {
+            Some(hardened) => Box::new(hardened.find_iter(text).map(|m| (m.start(), m.end()))),
+            None => Box::new(self.regex.find_iter(text).map(|m| (m.start(), m.end()))),
+        };
+        Box::new(prepared.distinct(matches, |&span| span))
+    }
+
+    /// Iterates over the groups of every match in the transformed text, with
+    /// the hardened engine when constant time matching is enabled. Matches
+    /// are skipped like `find_iter`.
+    fn captures_iter<'a>(&'a self, prepared: &'a Prepared<'a>) -> Box<dyn Iterator<Item = Captures<'a>> + 'a> {
+        let text = &*prepared.text;
+        let captures: Box<dyn Iterator<Item = Captures>> = match &self.hardened {
+            Some(hardened) => Box::new(hardened.captures_iter(text).map(Captures::ConstantTime)),
+            None => Box::new(self.regex.captures_iter(text).map(Captures::Auto)),
+        };
+        Box::new(prepared.distinct(captures, |capture| capture.get(0).unwrap()))
+    }
+
+    /// Finds the groups of the first match in the transformed text, the
+    /// hardened engine scans the rest of the string like `hardened_find`.
+    fn first_captures<'a>(&'a self, prepared: &'a Prepared<'a>) -> Option<Captures<'a>> {
+        match &self.hardened {
+            Some(_) => {
+                let mut first = None;
+                for capture in self.captures_iter(prepared) {
+                    first.get_or_insert(capture);
+                }
+                first
+            },
+            None => self.regex.captures(&prepared.text).map(Captures::Auto),
+        }
+    }
+
+    /// Finds the (start, end) byte offsets of every match in the original
+    /// string.
+    fn spans(&self, other: &str, limits: &Limits) -> PyResult<Vec<(usize, usize)>> {
+        limits.start(other.len())?;
+
+        let prepared = Prepared::new(other, &self.pre);
+
         let mut matches = Vec::new();
-        for m in self.regex.find_iter(other) {
-            matches.push((m.start(), m.end()));
+        for (start, end) in self.find_iter(&prepared) {
+            limits.check()?;
+            matches.push(prepared.original(start, end));
         }
-        matches
+        metrics::MATCHES.add(matches.len());
+        Ok(matches)
     }
 }
 
@@ -184,12 +1179,18 @@ struct PyRegexSet {
 #[pymethods]
 impl PyRegexSet {
     #[new]
-    fn new(pattern: Vec<&str>) -> PyResult<Self> {
+    #[args(semantics = "None")]
+    fn new(py: Python, pattern: Vec<&str>, semantics: Option<&PyAny>) -> PyResult<Self> {
+        let semantics = Semantics::extract(semantics)?.unwrap_or(SemanticsKind::Unicode);
+        let pattern = pattern
+            .into_iter()
+            .map(|p| expand_pattern(py, p, semantics))
+            .collect::<PyResult<Vec<String>>>()?;
         let set = RegexSet::new(pattern);
 
         let set = match set {
             Ok(s) => s,
-            Err(e) => return Err(PyValueError::new_err(format!("{:?}", e)))
+            Err(e) => return Err(compile_error(e))
         };
 
         Ok(PyRegexSet {
@@ -202,11 +1203,26 @@ impl PyRegexSet {
     /// Args:
     ///     other:
     ///         The other string to be matched against the compiled set.
+    ///     budget:
+    ///         An optional Budget this call takes its time and bytes from.
+    ///     token:
+    ///         An optional CancellationToken that can cancel this call.
     ///
     /// Returns:
     ///     A bool signifying if any patterns in the set match.
-    fn is_match(&self, other: &str) -> bool {
-        self.set.is_match(other)
+    #[args(budget = "None", token = "None")]
+    fn is_match(
+        &self,
+        other: &str,
+        budget: Option<PyRef<Budget>>,
+        token: Option<PyRef<CancellationToken>>,
+    ) -> PyResult<bool> {
+        let limits = Limits::new(budget.as_deref(), token.as_deref());
+        limits.start(other.len())?;
+
+        let matched = self.set.is_match(other);
+        metrics::MATCHES.add(matched as usize);
+        Ok(matched)
     }
 
     /// Matches the string against the compiled set which will give a list of
@@ -215,11 +1231,24 @@ impl PyRegexSet {
     /// Args:
     ///     other:
     ///         The other string to be matched against the compiled set.
+    ///     budget:
+    ///         An optional Budget this call takes its time and bytes from.
+    ///     token:
+    ///         An optional CancellationToken that can cancel this call.
     ///
     /// Returns:
     ///     A list of ints which relates the the index of the pattern that was
     ///     matched. The order of patterns is the same order as added.
-    fn find(&self, other: &str) -> Vec<usize> {
+    #[args(budget = "None", token = "None")]
+    fn find(
+        &self,
+        other: &str,
+        budget: Option<PyRef<Budget>>,
+        token: Option<PyRef<CancellationToken>>,
+    ) -> PyResult<Vec<usize>> {
+        let limits = Limits::new(budget.as_deref(), token.as_deref());
+        limits.start(other.len())?;
+
         let matches = self.set.matches(other);
 
         let mut out_matches = Vec::with_capacity(self.set.len());
@@ -227,26 +1256,268 @@ impl PyRegexSet {
             out_matches.push(match_)
         }
 
-        out_matches
+        metrics::MATCHES.add(out_matches.len());
+        Ok(out_matches)
     }
-}
 
+    /// Streams a file line by line matching every line against the compiled
+    /// set, counting how many lines each pattern matches in a single pass
+    /// without loading the file into memory.
+    ///
+    /// Lines that aren't valid UTF-8 have the invalid bytes replaced before
+    /// matching.
+    ///
+    /// Args:
+    ///     path:
+    ///         The path of the file to classify.
+    ///     token:
+    ///         An optional CancellationToken that can cancel this call.
+    ///     summary:
+    ///         Pass an amount of examples to return a ScanSummary counting
+    ///         matched lines per pattern and keeping only that many of the
+    ///         first matched lines.
+    ///
+    /// Returns:
+    ///     A list with a tuple of (match_count, first_line) for every pattern
+    ///     in the set in the order they were added, first_line being the
+    ///     1-based line number of the first matching line or None if the
+    ///     pattern never matched, or a ScanSummary when `summary` is given.
+    #[args(token = "None", summary = "None")]
+    fn classify_file(
+        &self,
+        py: Python,
+        path: &str,
+        token: Option<PyRef<CancellationToken>>,
+        summary: Option<usize>,
+    ) -> PyResult<PyObject> {
+        let file = File::open(path).map_err(|e| PyIOError::new_err(e.to_string()))?;
+        let limits = Limits::new(None, token.as_deref());
+
+        let (results, summary) = py.allow_threads(|| -> PyResult<_> {
+            let mut reader = BufReader::with_capacity(1 << 16, file);
+            let mut results: Vec<(usize, Option<usize>)> = vec![(0, None); self.set.len()];
+            let mut summary = summary.map(|limit| ScanSummary::new(self.set.len(), limit));
+            let mut buf = Vec::new();
+            let mut line_number = 0;
 
-fn list_captures(capture: regex::Captures) ->Vec<Option<String>> {
-    let mut new: Vec<Option<String>> = capture
-        .iter()
-        .map(|m| {
-            match m {
-                Some(thing) => {
-                    Some(thing.as_str().to_string())
+            loop {
+                buf.clear();
+                if reader.read_until(b'\n', &mut buf).map_err(|e| PyIOError::new_err(e.to_string()))? == 0 {
+                    break;
+                }
+                line_number += 1;
+
+                let line = String::from_utf8_lossy(trim_newline(&buf));
+                limits.start(line.len())?;
+                for i in self.set.matches(&line).iter() {
+                    metrics::MATCHES.inc();
+                    results[i].0 += 1;
+                    results[i].1.get_or_insert(line_number);
+                    if let Some(summary) = &mut summary {
+                        summary.add_line(i, line_number, || line.to_string());
+                    }
                 }
-                _ => None,
             }
-        })
-        .collect();
-    new.remove(0);
 
-    new
+            Ok((results, summary))
+        })?;
+
+        match summary {
+            Some(summary) => Ok(summary.into_py(py)),
+            None => Ok(results.into_py(py)),
+        }
+    }
+
+    /// Times every pattern in the set against a sample corpus, reporting
+    /// which patterns take up most of the matching time and how often each
+    /// one matches, to find the rules worth optimizing or removing.
+    ///
+    /// Every pattern is timed on its own, so the times add up to more than
+    /// matching the whole set would take, but how they rank against each
+    /// other still holds.
+    ///
+    /// Args:
+    ///     corpus:
+    ///         An iterable of strings to time the patterns against.
+    ///     every:
+    ///         Only time every `every`th string of the corpus, to sample a
+    ///         large corpus cheaply.
+    ///     token:
+    ///         An optional CancellationToken that can cancel this call.
+    ///
+    /// Returns:
+    ///     A list of dicts, one for every pattern with the slowest first,
+    ///     holding the `index` and `pattern`, the total `time_ms` spent
+    ///     matching it, its `share` of the time spent on every pattern, the
+    ///     amount of strings it matched as `hits` and the fraction of strings
+    ///     it matched as `hit_rate`.
+    #[args(every = "1", token = "None")]
+    fn profile(
+        &self,
+        py: Python,
+        corpus: &PyAny,
+        every: usize,
+        token: Option<PyRef<CancellationToken>>,
+    ) -> PyResult<PyObject> {
+        if every == 0 {
+            return Err(PyValueError::new_err("every must be at least 1"))
+        }
+
+        let members = self.set
+            .patterns()
+            .iter()
+            .map(|pattern| Regex::new(pattern).map_err(|e| PyValueError::new_err(format!("{:?}", e))))
+            .collect::<PyResult<Vec<Regex>>>()?;
+
+        let texts = corpus
+            .iter()?
+            .map(|text| text?.extract::<&str>())
+            .collect::<PyResult<Vec<&str>>>()?;
+
+        let limits = Limits::new(None, token.as_deref());
+        let report = py.allow_threads(|| {
+            profile::run(&members, texts.iter().copied(), every, &limits)
+        })?;
+
+        let total: f64 = report.members.iter().map(|m| m.time.as_secs_f64()).sum();
+
+        let mut out = Vec::with_capacity(report.members.len());
+        for member in report.members {
+            let time = member.time.as_secs_f64();
+
+            let dict = PyDict::new(py);
+            dict.set_item("index", member.index)?;
+            dict.set_item("pattern", &self.set.patterns()[member.index])?;
+            dict.set_item("time_ms", time * 1000.0)?;
+            dict.set_item("share", if total > 0.0 { time / total } else { 0.0 })?;
+            dict.set_item("hits", member.hits)?;
+            dict.set_item("hit_rate", if report.texts > 0 {
+                member.hits as f64 / report.texts as f64
+            } else {
+                0.0
+            })?;
+            out.push(dict);
+        }
+
+        Ok(PyList::new(py, out).into())
+    }
+}
+
+
+/// Finds the first match starting from `start` with the hardened engine,
+/// always scanning the rest of the string so the time taken doesn't depend
+/// on where (or if) the first match is found.
+fn hardened_find(regex: &meta::Regex, haystack: &str, start: usize) -> Option<regex_automata::Match> {
+    let mut first = None;
+    for m in regex.find_iter(Input::new(haystack).range(start..)) {
+        first.get_or_insert(m);
+    }
+
+    first
+}
+
+/// Expands the macros in a pattern, checks the result against the compile
+/// policy and rewrites it to follow the semantics, every pattern goes through
+/// this before it is compiled.
+fn expand_pattern(py: Python, pattern: &str, semantics: SemanticsKind) -> PyResult<String> {
+    let expanded = macros::expand(py, pattern)
+        .and_then(|pattern| policy::check(&pattern).map(|_| pattern))
+        .and_then(|pattern| semantics.apply(&pattern).map_err(PyValueError::new_err));
+
+    if expanded.is_err() {
+        metrics::COMPILE_ERRORS.inc();
+    }
+
+    expanded
+}
+
+/// Turns an error compiling a pattern into a ValueError, counting it in the
+/// metrics.
+fn compile_error(e: impl std::fmt::Debug) -> PyErr {
+    metrics::COMPILE_ERRORS.inc();
+    PyValueError::new_err(format!("{:?}", e))
+}
+
+/// Converts a byte offset into `text` to a character offset, which is what
+/// Python uses to index strings.
+fn char_offset(text: &str, byte: usize) -> usize {
+    text[..byte].chars().count()
+}
+
+/// Summarizes the matches of a single regex, `spans` being their byte
+/// offsets into `text` and `offsets` the offsets to report for them.
+fn summarize(text: &str, spans: &[(usize, usize)], offsets: &[(usize, usize)], limit: usize) -> ScanSummary {
+    let mut summary = ScanSummary::new(1, limit);
+    for ((start, end), offset) in spans.iter().zip(offsets) {
+        summary.add_match(0, *offset, || text[*start..*end].to_string());
+    }
+
+    summary
+}
+
+/// Converts ordered (start, end) byte offsets into `text` to character
+/// offsets, walking the text only once.
+fn char_spans(text: &str, spans: Vec<(usize, usize)>) -> Vec<(usize, usize)> {
+    let mut offsets = text.char_indices().map(|(i, _)| i).chain(std::iter::once(text.len()));
+    let mut index = 0;
+    let mut next = offsets.next();
+    let mut to_index = |byte: usize| {
+        while let Some(offset) = next {
+            if offset >= byte {
+                break;
+            }
+            index += 1;
+            next = offsets.next();
+        }
+        index
+    };
+
+    spans
+        .into_iter()
+        .map(|(start, end)| (to_index(start), to_index(end)))
+        .collect()
+}
+
+/// Builds a string from an iterable of code points given as ints or single
+/// character strings.
+fn codepoints_to_string(codepoints: &PyAny) -> PyResult<String> {
+    let mut text = String::new();
+    for (i, item) in codepoints.iter()?.enumerate() {
+        let item = item?;
+        let ch = if let Ok(s) = item.downcast::<PyString>() {
+            let s = s.to_str()?;
+            let mut chars = s.chars();
+            match (chars.next(), chars.next()) {
+                (Some(ch), None) => ch,
+                _ => return Err(PyValueError::new_err(format!(
+                    "expected a single character at index {}, got {:?}", i, s,
+                ))),
+            }
+        } else {
+            let cp: i64 = item.extract()?;
+            std::convert::TryFrom::try_from(cp)
+                .ok()
+                .and_then(std::char::from_u32)
+                .ok_or_else(|| PyValueError::new_err(format!(
+                    "invalid code point {} at index {}", cp, i,
+                )))?
+        };
+        text.push(ch);
+    }
+
+    Ok(text)
+}
+
+/// Removes the trailing `\n` or `\r\n` from a line.
+fn trim_newline(line: &[u8]) -> &[u8] {
+    let line = line.strip_suffix(b"\n").unwrap_or(line);
+    line.strip_suffix(b"\r").unwrap_or(line)
+}
+
+fn list_captures(capture: &preprocess::OriginalCaptures) ->Vec<Option<String>> {
+    (1..capture.len())
+        .map(|i| capture.index(i).map(|thing| thing.to_string()))
+        .collect()
 }
 
 /// Function that given a `regex_pattern` and an input `input_str` will produce
@@ -257,18 +1528,510 @@ fn list_captures(capture: regex::Captures) ->Vec<Option<String>> {
 ///         The regex pattern to be matched against a string.
 ///     other:
 ///         The other string to be matched against the compiled regex.
+///     token:
+///         An optional CancellationToken that can cancel this call.
 ///
 /// Returns:
 ///     A vector of tuples that contain (start_match, end_match+1).
 ///
+/// The compiled pattern is kept in a small cache so calling this repeatedly
+/// with the same pattern doesn't compile it every time.
+#[pyfunction(token = "None")]
+pub fn matches(
+    py: Python,
+    regex_pattern: &str,
+    other: &str,
+    token: Option<PyRef<CancellationToken>>,
+) -> PyResult<Vec<(usize, usize)>> {
+    let re = cache::get_or_compile(py, regex_pattern)?;
+
+    let limits = Limits::new(None, token.as_deref());
+    limits.start(other.len())?;
+    let matches = py.allow_threads(|| -> PyResult<_> {
+        let mut matches = Vec::new();
+        for m in re.find_iter(other) {
+            limits.check()?;
+            matches.push((m.start(), m.end()));
+        }
+        Ok(matches)
+    })?;
+
+    metrics::MATCHES.add(matches.len());
+    Ok(matches)
+}
+
+/// Registers a pattern macro, any `%{name}` in a pattern compiled after this
+/// is replaced by the string returned from calling `expander(name)`, wrapped
+/// in a non capturing group.
+///
+/// The expanded text can itself contain macros, `%{name}` is left as it is
+/// when no macro is registered under that name. To match a literal `%{name}`
+/// of a registered macro escape the brace as `%\{name}`.
+///
+/// Args:
+///     name:
+///         The name of the macro, this must only contain ASCII letters, digits
+///         and underscores and can't start with a digit.
+///     expander:
+///         A callable taking the macro name and returning the pattern text it
+///         expands to.
 #[pyfunction]
-pub fn matches(regex_pattern: &str, other: &str) -> Vec<(usize, usize)> {
-    let re = Regex::new(regex_pattern).unwrap();
-    let mut matches = Vec::new();
-    for m in re.find_iter(other) {
-        matches.push((m.start(), m.end()));
+pub fn register_macro(name: &str, expander: &PyAny) -> PyResult<()> {
+    macros::register(name, expander)?;
+    cache::clear()
+}
+
+
+/// Sets the limits every pattern compiled afterwards has to stay within,
+/// patterns going over a limit raise a `PolicyError` (a `ValueError`)
+/// instead of being compiled. Each call replaces the whole policy, so any
+/// limit left out is removed.
+///
+/// Args:
+///     max_groups:
+///         The most capture groups a pattern can have.
+///     max_pattern_len:
+///         The most characters a pattern can be, after macros are expanded.
+///     max_alternations:
+///         The most `|` alternations a pattern can have in total.
+#[pyfunction(max_groups = "None", max_pattern_len = "None", max_alternations = "None")]
+pub fn set_policy(
+    max_groups: Option<usize>,
+    max_pattern_len: Option<usize>,
+    max_alternations: Option<usize>,
+) -> PyResult<()> {
+    policy::set(policy::Policy { max_groups, max_pattern_len, max_alternations })?;
+    cache::clear()
+}
+
+/// Returns the current compile policy.
+///
+/// Returns:
+///     A dict of `max_groups`, `max_pattern_len` and `max_alternations`,
+///     with None for limits that aren't set.
+#[pyfunction]
+pub fn get_policy(py: Python) -> PyResult<PyObject> {
+    let policy = policy::get()?;
+
+    let dict = PyDict::new(py);
+    dict.set_item("max_groups", policy.max_groups)?;
+    dict.set_item("max_pattern_len", policy.max_pattern_len)?;
+    dict.set_item("max_alternations", policy.max_alternations)?;
+
+    Ok(dict.into())
+}
+
+
+/// Removes ANSI escape sequences, such as terminal colours and titles, from
+/// the string.
+///
+/// Args:
+///     text:
+///         The string to remove the escape sequences from.
+///
+/// Returns:
+///     A tuple of the cleaned string and a list mapping the index of every
+///     character in the cleaned string to its index in the original string,
+///     with one extra entry for the end of the string.
+#[pyfunction]
+pub fn strip_ansi(text: &str) -> (String, Vec<usize>) {
+    let output = preprocess::strip_ansi(text);
+    let map = output.char_map(text);
+    (output.text, map)
+}
+
+/// Removes control characters other than tabs, carriage returns and
+/// newlines from the string.
+///
+/// Args:
+///     text:
+///         The string to remove the control characters from.
+///
+/// Returns:
+///     A tuple of the cleaned string and a list mapping the index of every
+///     character in the cleaned string to its index in the original string,
+///     with one extra entry for the end of the string.
+#[pyfunction]
+pub fn strip_control(text: &str) -> (String, Vec<usize>) {
+    let output = preprocess::strip_control(text);
+    let map = output.char_map(text);
+    (output.text, map)
+}
+
+
+/// Reports which regions of a pattern are affected by inline flags like
+/// `(?i)`, `(?-u)` or `(?s:...)`, useful for highlighting flag scopes in an
+/// editor.
+///
+/// A `(?flags)` directive applies until the end of the group it is in, or
+/// the end of the pattern, including any alternation branches after it. A
+/// `(?flags:...)` group only applies to the contents of the group.
+///
+/// Args:
+///     pattern:
+///         The regex pattern to analyze.
+///
+/// Returns:
+///     A list of dicts, one for every flag directive in the order they
+///     appear, with the `directive` text, its `directive_span` (start, end),
+///     the `span` (start, end) of the pattern it affects and a dict of the
+///     `flags` it sets mapping each flag character to whether it is enabled.
+#[pyfunction]
+pub fn flag_analysis(py: Python, pattern: &str) -> PyResult<PyObject> {
+    let scopes = match flags::analyze(pattern) {
+        Ok(s) => s,
+        Err(e) => return Err(PyValueError::new_err(e)),
+    };
+
+    let span = |(start, end): (usize, usize)| (char_offset(pattern, start), char_offset(pattern, end));
+
+    let mut out = Vec::with_capacity(scopes.len());
+    for scope in scopes {
+        let flags = PyDict::new(py);
+        for (flag, enabled) in scope.flags {
+            flags.set_item(flag.to_string(), enabled)?;
+        }
+
+        let dict = PyDict::new(py);
+        dict.set_item("directive", &pattern[scope.directive.0..scope.directive.1])?;
+        dict.set_item("directive_span", span(scope.directive))?;
+        dict.set_item("span", span(scope.region))?;
+        dict.set_item("flags", flags)?;
+        out.push(dict);
+    }
+
+    Ok(PyList::new(py, out).into())
+}
+
+
+/// Joins a token stream produced by `Regex.tokenize` back into a string,
+/// the text of the tokens may have been changed but every token has to be
+/// kept in its original order, including the "end" token it finishes with.
+///
+/// Args:
+///     tokens:
+///         An iterable of (kind, text, start, end) tuples.
+///
+/// Returns:
+///     The joined text of every token.
+#[pyfunction]
+pub fn detokenize(tokens: &PyAny) -> PyResult<String> {
+    let mut out = String::new();
+    let mut expected = 0;
+    let mut ended = false;
+    for (i, token) in tokens.iter()?.enumerate() {
+        let (kind, text, start, end): (&str, &str, usize, usize) = token?.extract()?;
+        if ended {
+            return Err(PyValueError::new_err(format!(
+                "token {} comes after the end token, tokens can't be reordered", i,
+            )));
+        }
+        if start != expected {
+            return Err(PyValueError::new_err(format!(
+                "token {} starts at {} but should start at {}, tokens can't be removed or reordered",
+                i, start, expected,
+            )));
+        }
+
+        out.push_str(text);
+        expected = end;
+        ended = kind == "end";
+    }
+
+    if !ended {
+        return Err(PyValueError::new_err(
+            "tokens don't finish with an end token, tokens can't be removed or reordered",
+        ));
+    }
+
+    Ok(out)
+}
+
+/// Creates a matcher finding IP addresses within any of the given CIDR
+/// ranges, e.g. `["10.0.0.0/8", "fe80::/10"]`.
+///
+/// Addresses are located by their shape and then parsed and checked against
+/// the ranges natively, which is something a regex can't express.
+///
+/// Args:
+///     cidrs:
+///         A list of IPv4 or IPv6 CIDR ranges, a plain address is treated as
+///         a range containing only itself.
+///
+/// Returns:
+///     A CidrMatcher with `is_match`, `findall` and `matches` methods.
+#[pyfunction]
+pub fn ip_in_cidr_matcher(cidrs: Vec<&str>) -> PyResult<cidr::CidrMatcher> {
+    cidr::CidrMatcher::new(cidrs)
+}
+
+/// Reads the process's stdin line by line, matching every line against the
+/// pattern and writing each match straight to stdout without any of the
+/// data becoming Python objects, for pipelines like
+/// `tail -f app.log | python -c "import regex; regex.scan_stdin('ERROR.*')"`.
+///
+/// Input is read in large chunks and results are flushed as soon as
+/// there is no more input ready, the GIL is released while scanning. Lines
+/// don't have to be valid UTF-8, invalid bytes in matched text are replaced
+/// on output. Anything already read into `sys.stdin` is not seen.
+///
+/// Args:
+///     pattern:
+///         The regex pattern to match every line against.
+///     format:
+///         Either "jsonl" to write a JSON object with the 1-based `line`
+///         number, the `start` and `end` byte offsets within the line and
+///         the matched `text` for every match, or "text" to write them as
+///         `line:text` rows.
+///     token:
+///         An optional CancellationToken that can cancel the scan, it is
+///         checked between lines.
+///
+/// Returns:
+///     The amount of matches written, once stdin is closed or stdout stops
+///     being read.
+#[pyfunction(format = "\"jsonl\"", token = "None")]
+pub fn scan_stdin(
+    py: Python,
+    pattern: &str,
+    format: &str,
+    token: Option<PyRef<CancellationToken>>,
+) -> PyResult<usize> {
+    let format = stream::Format::from_name(format).ok_or_else(|| {
+        let names: Vec<&str> = stream::Format::ALL.iter().map(|f| f.name()).collect();
+        PyValueError::new_err(format!(
+            "unknown format {:?}, expected one of {}", format, names.join(", "),
+        ))
+    })?;
+
+    let pattern = expand_pattern(py, pattern, SemanticsKind::Unicode)?;
+    let regex = match regex::bytes::Regex::new(&pattern) {
+        Ok(r) => r,
+        Err(e) => return Err(compile_error(e))
+    };
+
+    // Anything Python has buffered has to come out before our output does.
+    let stdout = py.import("sys")?.getattr("stdout")?;
+    if !stdout.is_none() {
+        stdout.call_method0("flush")?;
+    }
+
+    let limits = Limits::new(None, token.as_deref());
+    py.allow_threads(|| {
+        let stdin = std::io::stdin();
+        let stdout = std::io::stdout();
+        stream::scan(&regex, stdin.lock(), stdout.lock(), format, &limits)
+    })
+}
+
+/// Describes the structured results returned by this module, so tools
+/// can generate code for or validate them without reverse engineering
+/// their shapes.
+///
+/// Every result has a `name`, a `kind` of "dict", "tuple" with its fields
+/// in positional order, "mapping" with a `key` and `value` field, or
+/// "jsonl" for JSON objects written as lines, the functions and methods
+/// it is `returned_by`, and its `fields`, each a dict of the field `name`
+/// and its `type` as written in `regex.pyi`. Results nested in another
+/// are referred to by their name, like `List[coverage_branch]`.
+///
+/// Args:
+///     name:
+///         The name of a single result to describe.
+///
+/// Returns:
+///     The description of the named result, or a dict of every result by
+///     name when no name is given.
+#[pyfunction(name = "None")]
+pub fn schema(py: Python, name: Option<&str>) -> PyResult<PyObject> {
+    if let Some(name) = name {
+        return schema::get(name)?.to_dict(py);
+    }
+
+    let dict = PyDict::new(py);
+    for shape in schema::SHAPES {
+        dict.set_item(shape.name, shape.to_dict(py)?)?;
+    }
+
+    Ok(dict.into())
+}
+
+/// Reports which of the process wide registries are currently locked by
+/// some thread, to help debug a `LockTimeout`.
+///
+/// Returns:
+///     A dict of the registry name to whether it is locked.
+#[pyfunction]
+pub fn lock_status(py: Python) -> PyResult<PyObject> {
+    let dict = PyDict::new(py);
+    dict.set_item(macros::registry().name(), macros::registry().is_locked())?;
+    dict.set_item(policy::POLICY.name(), policy::POLICY.is_locked())?;
+    dict.set_item(cache::cache().name(), cache::cache().is_locked())?;
+
+    Ok(dict.into())
+}
+
+/// Sets how long to wait for one of the process wide registries, like the
+/// pattern macros or the compile policy, before giving up with
+/// `LockTimeout`.
+///
+/// Args:
+///     ms:
+///         The amount of milliseconds to wait, 5000 by default.
+#[pyfunction]
+pub fn set_lock_timeout(ms: u64) {
+    guard::set_timeout(std::time::Duration::from_millis(ms));
+}
+
+/// Times this crate against Python's `re` on the same pattern and haystack
+/// and checks both find the same matches, to validate a migration from `re`
+/// or to report a discrepancy.
+///
+/// Both engines find every match `iterations` times, collecting them into a
+/// list each time. The pattern is compiled once by each engine before
+/// timing starts, this crate uses the same cache as `regex.matches`.
+///
+/// Args:
+///     pattern:
+///         The regex pattern, which has to be valid for both engines. Pattern
+///         macros are expanded before either engine compiles it.
+///     haystack:
+///         The string to search.
+///     iterations:
+///         How many times each engine searches the haystack.
+///     token:
+///         An optional CancellationToken that can cancel this call, it is
+///         checked between iterations.
+///
+/// Returns:
+///     A dict with the `pattern`, the `iterations`, the total `regex_ms` and
+///     `re_ms`, the `speedup` of this crate over `re`, the `regex_matches`
+///     and `re_matches` counts, whether the matches are `equal`, and the
+///     `first_difference` as a dict of the match `index` and the `regex` and
+///     `re` char spans at it, `None` when an engine has no match there, or
+///     `None` when the matches are equal.
+#[pyfunction(iterations = "100", token = "None")]
+pub fn compare_with_re(
+    py: Python,
+    pattern: &str,
+    haystack: &str,
+    iterations: usize,
+    token: Option<PyRef<CancellationToken>>,
+) -> PyResult<PyObject> {
+    if iterations == 0 {
+        return Err(PyValueError::new_err("iterations must be at least 1"));
     }
-    matches
+
+    let regex = cache::get_or_compile(py, pattern)?;
+    let limits = Limits::new(None, token.as_deref());
+    let comparison = compare::run(py, &regex, haystack, iterations, &limits)?;
+
+    let time = comparison.time.as_secs_f64();
+    let re_time = comparison.re_time.as_secs_f64();
+
+    let first_difference = match comparison.first_difference() {
+        Some(index) => {
+            let dict = PyDict::new(py);
+            dict.set_item("index", index)?;
+            dict.set_item("regex", comparison.spans.get(index))?;
+            dict.set_item("re", comparison.re_spans.get(index))?;
+            dict.into_py(py)
+        },
+        None => py.None(),
+    };
+
+    let out = PyDict::new(py);
+    out.set_item("pattern", pattern)?;
+    out.set_item("iterations", iterations)?;
+    out.set_item("regex_ms", time * 1000.0)?;
+    out.set_item("re_ms", re_time * 1000.0)?;
+    out.set_item("speedup", if time > 0.0 { re_time / time } else { 0.0 })?;
+    out.set_item("regex_matches", comparison.spans.len())?;
+    out.set_item("re_matches", comparison.re_spans.len())?;
+    out.set_item("equal", first_difference.is_none(py))?;
+    out.set_item("first_difference", first_difference)?;
+
+    Ok(out.into())
+}
+
+/// Renders counters of the regex workload of the whole process in the
+/// Prometheus text exposition format, ready to be served from a metrics
+/// endpoint.
+///
+/// The counters are collected natively across every compiled object and
+/// module function: searches, matches, bytes scanned, pattern cache hits
+/// and misses, and compile errors. They count from when the module was
+/// imported and are never reset.
+///
+/// Returns:
+///     The metrics as a string.
+#[pyfunction]
+pub fn metrics_text() -> String {
+    metrics::render()
+}
+
+/// Every capability of the library and whether it is available in this
+/// build, new capabilities are added here as they are added to the library
+/// so code can feature detect any of them.
+const FEATURES: &[(&str, bool)] = &[
+    // Engines and build options that aren't compiled into this build.
+    ("fancy", false),
+    ("arrow", false),
+    ("parallel", false),
+    ("abi3", false),
+
+    ("unicode", true),
+    ("case_conversion", true),
+    ("constant_time", true),
+    ("macros", true),
+    ("coverage", true),
+    ("path_index", true),
+    ("budget", true),
+    ("format_matches", true),
+    ("fingerprint", true),
+    ("preprocess", true),
+    ("strip_ansi", true),
+    ("strip_control", true),
+    ("classify_file", true),
+    ("validate_template", true),
+    ("flag_analysis", true),
+    ("codepoints", true),
+    ("top_matches", true),
+    ("tokenize", true),
+    ("cidr", true),
+    ("policy", true),
+    ("match_ids", true),
+    ("scan_stdin", true),
+    ("cancellation", true),
+    ("utf16", true),
+    ("summary", true),
+    ("ascii_semantics", true),
+    ("profile", true),
+    ("metrics", true),
+    ("match_within_group", true),
+    ("compare_with_re", true),
+    ("lock_guard", true),
+    ("schema", true),
+];
+
+/// Reports which capabilities are available in this build of the library,
+/// so code using it can pick a code path at runtime instead of catching
+/// ImportError or AttributeError.
+///
+/// Returns:
+///     A dict mapping the name of every capability to a bool of whether it
+///     is available, `case_conversion` being the `\U`, `\L` and `\E`
+///     replacement template syntax. `fancy` (the backtracking engine),
+///     `arrow` (Arrow array input) and `parallel` (multi-threaded scanning)
+///     are never available in this build.
+#[pyfunction]
+pub fn features(py: Python) -> PyResult<PyObject> {
+    let dict = PyDict::new(py);
+    for (name, available) in FEATURES {
+        dict.set_item(*name, *available)?;
+    }
+
+    Ok(dict.into())
 }
 
 
@@ -276,9 +2039,35 @@ pub fn matches(regex_pattern: &str, other: &str) -> Vec<(usize, usize)> {
 /// Wraps all our existing pyobjects together in the module
 ///
 #[pymodule]
-fn regex(_py: Python, m: &PyModule) -> PyResult<()> {
+fn regex(py: Python, m: &PyModule) -> PyResult<()> {
     m.add_class::<PyRegex>()?;
     m.add_class::<PyRegexSet>()?;
+    m.add_class::<path_index::PathIndex>()?;
+    m.add_class::<Budget>()?;
+    m.add_class::<cidr::CidrMatcher>()?;
+    m.add_class::<Engine>()?;
+    m.add_class::<Semantics>()?;
+    m.add_class::<CancellationToken>()?;
+    m.add_class::<ScanSummary>()?;
+    m.add("BudgetExhausted", py.get_type::<limits::BudgetExhausted>())?;
+    m.add("Cancelled", py.get_type::<limits::Cancelled>())?;
+    m.add("LockTimeout", py.get_type::<guard::LockTimeout>())?;
+    m.add("PolicyError", py.get_type::<policy::PolicyError>())?;
     m.add_function(wrap_pyfunction!(matches, m)?)?;
+    m.add_function(wrap_pyfunction!(register_macro, m)?)?;
+    m.add_function(wrap_pyfunction!(set_policy, m)?)?;
+    m.add_function(wrap_pyfunction!(get_policy, m)?)?;
+    m.add_function(wrap_pyfunction!(strip_ansi, m)?)?;
+    m.add_function(wrap_pyfunction!(strip_control, m)?)?;
+    m.add_function(wrap_pyfunction!(flag_analysis, m)?)?;
+    m.add_function(wrap_pyfunction!(detokenize, m)?)?;
+    m.add_function(wrap_pyfunction!(ip_in_cidr_matcher, m)?)?;
+    m.add_function(wrap_pyfunction!(scan_stdin, m)?)?;
+    m.add_function(wrap_pyfunction!(schema, m)?)?;
+    m.add_function(wrap_pyfunction!(lock_status, m)?)?;
+    m.add_function(wrap_pyfunction!(set_lock_timeout, m)?)?;
+    m.add_function(wrap_pyfunction!(compare_with_re, m)?)?;
+    m.add_function(wrap_pyfunction!(metrics_text, m)?)?;
+    m.add_function(wrap_pyfunction!(features, m)?)?;
     Ok(())
 }
@@ -0,0 +1,227 @@
+use pyo3::prelude::*;
+use pyo3::create_exception;
+use pyo3::exceptions::PyRuntimeError;
+
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use crate::metrics;
+
+
+create_exception!(regex, BudgetExhausted, PyRuntimeError);
+create_exception!(regex, Cancelled, PyRuntimeError);
+
+/// A resource budget shared across several matching calls, every call the
+/// budget is passed to takes from the same time and byte allowance and
+/// raises `BudgetExhausted` once either of them runs out.
+///
+/// This gives a single overall cap to something like a web request running
+/// many regex evaluations rather than limiting each call on its own.
+///
+/// The time allowance is taken from by how long every call the budget is
+/// passed to takes, time spent outside of them isn't counted. The byte
+/// allowance is taken from by the length of every string scanned, a call
+/// scanning more bytes than are left fails without taking any. Time is
+/// checked before every call and between matches, a single search that is
+/// already running won't be interrupted.
+///
+/// Args:
+///     ms:
+///         The amount of milliseconds the calls may take in total, None for
+///         no time limit.
+///     bytes:
+///         The amount of bytes the calls may scan in total, None for no
+///         byte limit.
+#[pyclass]
+pub struct Budget {
+    time: Option<Duration>,
+
+    /// The nanoseconds taken by the calls that have finished.
+    used: AtomicU64,
+
+    bytes: Option<AtomicUsize>,
+    exhausted: AtomicBool,
+}
+
+#[pymethods]
+impl Budget {
+    #[new]
+    #[args(ms = "None", bytes = "None")]
+    fn new(ms: Option<u64>, bytes: Option<usize>) -> Self {
+        Budget {
+            time: ms.map(Duration::from_millis),
+            used: AtomicU64::new(0),
+            bytes: bytes.map(AtomicUsize::new),
+            exhausted: AtomicBool::new(false),
+        }
+    }
+
+    /// The amount of milliseconds left in the budget, None if there is no
+    /// time limit.
+    #[getter]
+    fn remaining_ms(&self) -> Option<u64> {
+        self.time.map(|time| time.saturating_sub(self.used()).as_millis() as u64)
+    }
+
+    /// The amount of bytes left in the budget, None if there is no byte
+    /// limit.
+    #[getter]
+    fn remaining_bytes(&self) -> Option<usize> {
+        self.bytes.as_ref().map(|bytes| bytes.load(Ordering::Relaxed))
+    }
+
+    /// If the budget has run out.
+    #[getter]
+    fn exhausted(&self) -> bool {
+        self.is_exhausted(Duration::ZERO)
+    }
+}
+
+impl Budget {
+    fn used(&self) -> Duration {
+        Duration::from_nanos(self.used.load(Ordering::Relaxed))
+    }
+
+    /// Takes the time a call took from the budget once it has finished.
+    fn spend(&self, time: Duration) {
+        self.used.fetch_add(time.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    /// If the budget has run out, counting the time a call has been
+    /// `running` for so far.
+    fn is_exhausted(&self, running: Duration) -> bool {
+        if self.exhausted.load(Ordering::Relaxed) {
+            return true;
+        }
+
+        matches!(self.time, Some(time) if self.used() + running >= time)
+    }
+
+    /// Takes `len` bytes from the budget before scanning a string, failing
+    /// if the time or bytes have run out.
+    fn charge(&self, len: usize, running: Duration) -> PyResult<()> {
+        self.check(running)?;
+
+        let bytes = match &self.bytes {
+            Some(bytes) => bytes,
+            None => return Ok(()),
+        };
+
+        let taken = bytes.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |left| {
+            left.checked_sub(len)
+        });
+
+        if let Err(left) = taken {
+            return Err(BudgetExhausted::new_err(format!(
+                "budget has {} bytes left, too few to scan {} more bytes", left, len,
+            )))
+        }
+
+        Ok(())
+    }
+
+    /// Fails if the time has run out.
+    fn check(&self, running: Duration) -> PyResult<()> {
+        if self.is_exhausted(running) {
+            self.exhausted.store(true, Ordering::Relaxed);
+            return Err(BudgetExhausted::new_err("budget has been exhausted"))
+        }
+
+        Ok(())
+    }
+}
+
+/// A token that can be passed to long running calls and cancelled from
+/// another thread, making the calls it was passed to raise `Cancelled`,
+/// such as when the client a server is scanning for has disconnected.
+///
+/// Calls check the token before they start and between every match, line
+/// or string they process, a single search that is already running won't
+/// be interrupted. Calls release the GIL while they scan so another thread
+/// can cancel them while they run, apart from single searches like
+/// `is_match` and `find` which only check the token before they start.
+#[pyclass]
+pub struct CancellationToken {
+    cancelled: AtomicBool,
+}
+
+#[pymethods]
+impl CancellationToken {
+    #[new]
+    fn new() -> Self {
+        CancellationToken {
+            cancelled: AtomicBool::new(false),
+        }
+    }
+
+    /// Cancels every call the token has been passed to, this can't be
+    /// undone.
+    fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// If the token has been cancelled.
+    #[getter]
+    fn cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+}
+
+impl CancellationToken {
+    fn check(&self) -> PyResult<()> {
+        if self.cancelled() {
+            return Err(Cancelled::new_err("call has been cancelled"))
+        }
+
+        Ok(())
+    }
+}
+
+/// The limits a single matching call runs under, created when the call
+/// starts and dropped when it returns, which takes the time it took from
+/// the budget.
+pub struct Limits<'a> {
+    budget: Option<&'a Budget>,
+    token: Option<&'a CancellationToken>,
+    started: Instant,
+}
+
+impl<'a> Limits<'a> {
+    pub fn new(budget: Option<&'a Budget>, token: Option<&'a CancellationToken>) -> Self {
+        Limits { budget, token, started: Instant::now() }
+    }
+
+    /// Called before scanning a string of `len` bytes, this also counts the
+    /// search in the process metrics.
+    pub fn start(&self, len: usize) -> PyResult<()> {
+        if let Some(token) = self.token {
+            token.check()?;
+        }
+        metrics::searched(len);
+
+        match self.budget {
+            Some(budget) => budget.charge(len, self.started.elapsed()),
+            None => Ok(()),
+        }
+    }
+
+    /// Called between matches while iterating over a string.
+    pub fn check(&self) -> PyResult<()> {
+        if let Some(token) = self.token {
+            token.check()?;
+        }
+
+        match self.budget {
+            Some(budget) => budget.check(self.started.elapsed()),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Limits<'_> {
+    fn drop(&mut self) {
+        if let Some(budget) = self.budget {
+            budget.spend(self.started.elapsed());
+        }
+    }
+}
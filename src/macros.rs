@@ -0,0 +1,104 @@
+use pyo3::prelude::*;
+use pyo3::exceptions::{PyTypeError, PyValueError};
+
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::guard::Guarded;
+
+
+/// How deep macros are allowed to expand into other macros before giving up,
+/// this stops self referencing macros from recursing forever.
+const MAX_EXPANSION_DEPTH: usize = 32;
+
+pub fn registry() -> &'static Guarded<HashMap<String, PyObject>> {
+    static MACROS: OnceLock<Guarded<HashMap<String, PyObject>>> = OnceLock::new();
+    MACROS.get_or_init(|| Guarded::new("macros", HashMap::new()))
+}
+
+fn is_macro_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(c) if c == '_' || c.is_ascii_alphabetic() => {},
+        _ => return false,
+    }
+
+    chars.all(|c| c == '_' || c.is_ascii_alphanumeric())
+}
+
+/// Expands all `%{NAME}` macros in the pattern using the registered
+/// expanders, patterns without any macros are returned unchanged. Every
+/// expansion is wrapped in a non capturing group so alternations in it stay
+/// inside it, names without an expander are left as they are.
+pub fn expand(py: Python, pattern: &str) -> PyResult<String> {
+    expand_depth(py, pattern, 0)
+}
+
+fn expand_depth(py: Python, pattern: &str, depth: usize) -> PyResult<String> {
+    if !pattern.contains("%{") {
+        return Ok(pattern.to_string());
+    }
+
+    if depth >= MAX_EXPANSION_DEPTH {
+        return Err(PyValueError::new_err(format!(
+            "pattern macros nested more than {} levels deep, is a macro referencing itself?",
+            MAX_EXPANSION_DEPTH,
+        )))
+    }
+
+    let mut expanded = String::with_capacity(pattern.len());
+    let mut rest = pattern;
+    while let Some(start) = rest.find("%{") {
+        expanded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let name = match rest[2..].find('}') {
+            Some(end) if is_macro_name(&rest[2..2 + end]) => &rest[2..2 + end],
+            _ => {
+                expanded.push_str("%{");
+                rest = &rest[2..];
+                continue;
+            },
+        };
+
+        // The expander is cloned out of the registry so the lock isn't held
+        // while calling back into Python.
+        let expander = match registry().lock()?.get(name) {
+            Some(expander) => expander.clone_ref(py),
+            None => {
+                expanded.push_str("%{");
+                rest = &rest[2..];
+                continue;
+            },
+        };
+
+        let text: String = expander.call1(py, (name,))?.extract(py)?;
+        expanded.push_str("(?:");
+        expanded.push_str(&expand_depth(py, &text, depth + 1)?);
+        expanded.push(')');
+        rest = &rest[name.len() + 3..];
+    }
+    expanded.push_str(rest);
+
+    Ok(expanded)
+}
+
+/// Registers the expander for a pattern macro, replacing any previous one.
+pub fn register(name: &str, expander: &PyAny) -> PyResult<()> {
+    if !is_macro_name(name) {
+        return Err(PyValueError::new_err(format!(
+            "invalid macro name {:?}, names may only contain ASCII letters, digits and underscores",
+            name,
+        )))
+    }
+
+    if !expander.is_callable() {
+        return Err(PyTypeError::new_err("macro expander must be callable"))
+    }
+
+    registry()
+        .lock()?
+        .insert(name.to_string(), expander.into());
+
+    Ok(())
+}
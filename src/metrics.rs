@@ -0,0 +1,69 @@
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+
+/// A process wide counter exported by `metrics_text`.
+pub struct Counter {
+    name: &'static str,
+    help: &'static str,
+    value: AtomicU64,
+}
+
+impl Counter {
+    const fn new(name: &'static str, help: &'static str) -> Self {
+        Counter { name, help, value: AtomicU64::new(0) }
+    }
+
+    pub fn add(&self, n: usize) {
+        self.value.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    pub fn inc(&self) {
+        self.add(1);
+    }
+}
+
+pub static SEARCHES: Counter = Counter::new(
+    "regex_searches_total",
+    "Strings, lines and files searched.",
+);
+pub static MATCHES: Counter = Counter::new(
+    "regex_matches_total",
+    "Matches found by searches.",
+);
+pub static BYTES_SCANNED: Counter = Counter::new(
+    "regex_bytes_scanned_total",
+    "Bytes of text searched.",
+);
+pub static CACHE_HITS: Counter = Counter::new(
+    "regex_cache_hits_total",
+    "Patterns found already compiled in the pattern cache.",
+);
+pub static CACHE_MISSES: Counter = Counter::new(
+    "regex_cache_misses_total",
+    "Patterns compiled because they weren't in the pattern cache.",
+);
+pub static COMPILE_ERRORS: Counter = Counter::new(
+    "regex_compile_errors_total",
+    "Patterns that failed to compile, including ones rejected by the policy.",
+);
+
+const ALL: [&Counter; 6] = [&SEARCHES, &MATCHES, &BYTES_SCANNED, &CACHE_HITS, &CACHE_MISSES, &COMPILE_ERRORS];
+
+/// Counts a search of `len` bytes.
+pub fn searched(len: usize) {
+    SEARCHES.inc();
+    BYTES_SCANNED.add(len);
+}
+
+/// Renders every counter in the Prometheus text exposition format.
+pub fn render() -> String {
+    let mut out = String::new();
+    for counter in ALL.iter() {
+        let _ = writeln!(out, "# HELP {} {}", counter.name, counter.help);
+        let _ = writeln!(out, "# TYPE {} counter", counter.name);
+        let _ = writeln!(out, "{} {}", counter.name, counter.value.load(Ordering::Relaxed));
+    }
+
+    out
+}
@@ -0,0 +1,199 @@
+use pyo3::prelude::*;
+use pyo3::class::PySequenceProtocol;
+use pyo3::exceptions::PyValueError;
+
+use fst::{IntoStreamer, Set, Streamer};
+
+use regex_automata::dfa::dense::{self, DFA};
+use regex_automata::dfa::{Automaton, StartKind};
+use regex_automata::util::primitives::StateID;
+use regex_automata::util::{start, syntax};
+use regex_automata::Anchored;
+
+use crate::engine::SemanticsKind;
+use crate::limits::{CancellationToken, Limits};
+use crate::metrics;
+
+
+/// The most memory a single query DFA is allowed to use.
+const DFA_SIZE_LIMIT: usize = 10 * (1 << 20);
+
+/// Index a large list of file paths so they can be searched with regex
+/// patterns far faster than matching every path one at a time.
+///
+/// Paths are stored in an FST (finite state transducer) so prefixes and
+/// suffixes shared between paths are only stored once. A query compiles its
+/// pattern to a DFA and walks it along the FST, so a prefix shared by many
+/// paths is only matched once, and every path below a prefix the DFA can no
+/// longer match is skipped without being looked at.
+///
+/// Queries are case insensitive unless `case_insensitive=False` is passed,
+/// and like `Regex.is_match` a path matches if the pattern matches anywhere
+/// within it, use `^` and `$` to anchor a pattern to the whole path.
+/// Pattern macros are expanded and the compile policy is applied like for
+/// every other pattern.
+///
+///
+/// # Limitations
+/// Queries are compiled to a full DFA, so patterns using Unicode word
+/// boundaries (`\b`) aren't supported, the ASCII `(?-u:\b)` can be used
+/// instead. Patterns that would produce a DFA larger than 10MB are rejected.
+#[pyclass]
+pub struct PathIndex {
+    paths: Set<Vec<u8>>,
+
+    /// The total length of every path, counted as scanned by every query.
+    bytes: usize,
+    case_insensitive: bool,
+}
+
+#[pymethods]
+impl PathIndex {
+    #[new]
+    #[args(case_insensitive = "true")]
+    fn new(paths: &PyAny, case_insensitive: bool) -> PyResult<Self> {
+        let mut paths = paths
+            .iter()?
+            .map(|path| path?.extract::<String>())
+            .collect::<PyResult<Vec<String>>>()?;
+        paths.sort_unstable();
+        paths.dedup();
+
+        let bytes = paths.iter().map(String::len).sum();
+        let paths = Set::from_iter(paths)
+            .map_err(|e| PyValueError::new_err(e.to_string()))?;
+
+        Ok(PathIndex { paths, bytes, case_insensitive })
+    }
+
+    /// Searches the index for every path matching the pattern.
+    ///
+    /// Args:
+    ///     pattern:
+    ///         The regex pattern to match the paths against.
+    ///     token:
+    ///         An optional CancellationToken that can cancel this call.
+    ///
+    /// Returns:
+    ///     A list of the matching paths in sorted order.
+    #[args(token = "None")]
+    fn search(
+        &self,
+        py: Python,
+        pattern: &str,
+        token: Option<PyRef<CancellationToken>>,
+    ) -> PyResult<Vec<String>> {
+        let limits = Limits::new(None, token.as_deref());
+        limits.check()?;
+
+        let pattern = crate::expand_pattern(py, pattern, SemanticsKind::Unicode)?;
+
+        let dfa = dense::Builder::new()
+            .syntax(syntax::Config::new().case_insensitive(self.case_insensitive))
+            .configure(
+                dense::Config::new()
+                    .start_kind(StartKind::Unanchored)
+                    .dfa_size_limit(Some(DFA_SIZE_LIMIT))
+                    .determinize_size_limit(Some(DFA_SIZE_LIMIT))
+            )
+            .build(&pattern);
+
+        let query = match dfa.map_err(|e| e.to_string()).and_then(Query::new) {
+            Ok(query) => query,
+            Err(e) => {
+                metrics::COMPILE_ERRORS.inc();
+                return Err(PyValueError::new_err(e))
+            },
+        };
+
+        // Every path is searched by the query, even though the prefixes they
+        // share are only walked once.
+        metrics::SEARCHES.add(self.paths.len());
+        metrics::BYTES_SCANNED.add(self.bytes);
+
+        let found = py.allow_threads(|| -> PyResult<_> {
+            let mut found = Vec::new();
+            let mut stream = self.paths.search(&query).into_stream();
+            while let Some(path) = stream.next() {
+                limits.check()?;
+                // Keys are only ever built from Python strings.
+                found.push(String::from_utf8(path.to_vec()).unwrap());
+            }
+            Ok(found)
+        })?;
+
+        metrics::MATCHES.add(found.len());
+        Ok(found)
+    }
+}
+
+#[pyproto]
+impl PySequenceProtocol for PathIndex {
+    fn __len__(&self) -> usize {
+        self.paths.len()
+    }
+}
+
+/// An unanchored query DFA walked along the FST.
+struct Query {
+    dfa: DFA<Vec<u32>>,
+    start: StateID,
+}
+
+/// Where the query is after the bytes of a prefix, once the prefix matches
+/// every path starting with it matches no matter where the DFA goes next.
+#[derive(Clone, Copy)]
+enum QueryState {
+    Searching(StateID),
+    Matched,
+}
+
+impl Query {
+    fn new(dfa: DFA<Vec<u32>>) -> Result<Self, String> {
+        let start = dfa
+            .start_state(&start::Config::new().anchored(Anchored::No))
+            .map_err(|e| e.to_string())?;
+
+        Ok(Query { dfa, start })
+    }
+}
+
+impl fst::Automaton for Query {
+    type State = QueryState;
+
+    fn start(&self) -> QueryState {
+        QueryState::Searching(self.start)
+    }
+
+    fn is_match(&self, state: &QueryState) -> bool {
+        match *state {
+            QueryState::Searching(id) => self.dfa.is_match_state(self.dfa.next_eoi_state(id)),
+            QueryState::Matched => true,
+        }
+    }
+
+    fn can_match(&self, state: &QueryState) -> bool {
+        match *state {
+            QueryState::Searching(id) => !self.dfa.is_dead_state(id),
+            QueryState::Matched => true,
+        }
+    }
+
+    fn will_always_match(&self, state: &QueryState) -> bool {
+        matches!(state, QueryState::Matched)
+    }
+
+    fn accept(&self, state: &QueryState, byte: u8) -> QueryState {
+        match *state {
+            QueryState::Searching(id) => {
+                let next = self.dfa.next_state(id, byte);
+                if self.dfa.is_match_state(next) {
+                    QueryState::Matched
+                } else {
+                    QueryState::Searching(next)
+                }
+            },
+            QueryState::Matched => QueryState::Matched,
+        }
+    }
+}
@@ -0,0 +1,114 @@
+use pyo3::prelude::*;
+use pyo3::create_exception;
+use pyo3::exceptions::PyValueError;
+
+use regex_syntax::ast::{Ast, GroupKind};
+use regex_syntax::ast::parse::Parser;
+
+use crate::guard::Guarded;
+
+
+create_exception!(regex, PolicyError, PyValueError);
+
+/// Limits every pattern has to stay within to be compiled, `None` meaning
+/// no limit.
+#[derive(Clone, Copy)]
+pub struct Policy {
+    pub max_groups: Option<usize>,
+    pub max_pattern_len: Option<usize>,
+    pub max_alternations: Option<usize>,
+}
+
+pub static POLICY: Guarded<Policy> = Guarded::new("policy", Policy {
+    max_groups: None,
+    max_pattern_len: None,
+    max_alternations: None,
+});
+
+pub fn get() -> PyResult<Policy> {
+    Ok(*POLICY.lock()?)
+}
+
+pub fn set(policy: Policy) -> PyResult<()> {
+    *POLICY.lock()? = policy;
+    Ok(())
+}
+
+/// Checks a pattern against the current policy, failing with a
+/// `PolicyError` describing the first limit it goes over.
+pub fn check(pattern: &str) -> PyResult<()> {
+    let policy = get()?;
+
+    if let Some(max) = policy.max_pattern_len {
+        let len = pattern.chars().count();
+        if len > max {
+            return Err(PolicyError::new_err(format!(
+                "pattern is {} characters long which is over the limit of {}", len, max,
+            )))
+        }
+    }
+
+    if policy.max_groups.is_none() && policy.max_alternations.is_none() {
+        return Ok(());
+    }
+
+    // Patterns that don't parse are left for the compiler to report.
+    let ast = match Parser::new().parse(pattern) {
+        Ok(ast) => ast,
+        Err(_) => return Ok(()),
+    };
+
+    let mut counts = Counts { groups: 0, alternations: 0 };
+    counts.walk(&ast);
+
+    if let Some(max) = policy.max_groups {
+        if counts.groups > max {
+            return Err(PolicyError::new_err(format!(
+                "pattern has {} capture groups which is over the limit of {}", counts.groups, max,
+            )))
+        }
+    }
+
+    if let Some(max) = policy.max_alternations {
+        if counts.alternations > max {
+            return Err(PolicyError::new_err(format!(
+                "pattern has {} alternations which is over the limit of {}", counts.alternations, max,
+            )))
+        }
+    }
+
+    Ok(())
+}
+
+struct Counts {
+    groups: usize,
+
+    /// The amount of `|` operators.
+    alternations: usize,
+}
+
+impl Counts {
+    fn walk(&mut self, ast: &Ast) {
+        match ast {
+            Ast::Group(group) => {
+                if !matches!(group.kind, GroupKind::NonCapturing(_)) {
+                    self.groups += 1;
+                }
+                self.walk(&group.ast);
+            },
+            Ast::Repetition(rep) => self.walk(&rep.ast),
+            Ast::Concat(concat) => {
+                for ast in concat.asts.iter() {
+                    self.walk(ast);
+                }
+            },
+            Ast::Alternation(alt) => {
+                self.alternations += alt.asts.len() - 1;
+                for ast in alt.asts.iter() {
+                    self.walk(ast);
+                }
+            },
+            _ => {},
+        }
+    }
+}
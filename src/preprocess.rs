@@ -0,0 +1,348 @@
+use std::borrow::Cow;
+
+use crate::engine::Captures;
+use crate::template::Groups;
+
+
+/// A native transform applied to strings before they are matched.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Transform {
+    /// Removes ANSI escape sequences such as terminal colours.
+    StripAnsi,
+
+    /// Removes control characters other than tabs, carriage returns and
+    /// newlines.
+    StripControl,
+
+    /// Collapses every run of whitespace into a single space.
+    CollapseWs,
+
+    /// Lowercases every character.
+    Lowercase,
+}
+
+impl Transform {
+    pub const ALL: [Transform; 4] = [
+        Transform::StripAnsi,
+        Transform::StripControl,
+        Transform::CollapseWs,
+        Transform::Lowercase,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Transform::StripAnsi => "strip_ansi",
+            Transform::StripControl => "strip_control",
+            Transform::CollapseWs => "collapse_ws",
+            Transform::Lowercase => "lowercase",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Transform::ALL.iter().copied().find(|t| t.name() == name)
+    }
+
+    fn apply(self, input: &str) -> Output {
+        match self {
+            Transform::StripAnsi => strip_ansi(input),
+            Transform::StripControl => strip_control(input),
+            Transform::CollapseWs => collapse_ws(input),
+            Transform::Lowercase => lowercase(input),
+        }
+    }
+}
+
+/// The output of a transform, every byte of the output text has the byte
+/// range of the input it was produced from.
+pub struct Output {
+    pub text: String,
+    pub spans: Vec<(usize, usize)>,
+}
+
+impl Output {
+    fn with_capacity(capacity: usize) -> Self {
+        Output {
+            text: String::with_capacity(capacity),
+            spans: Vec::with_capacity(capacity),
+        }
+    }
+
+    fn push(&mut self, text: &str, from: (usize, usize)) {
+        self.text.push_str(text);
+        self.spans.extend(std::iter::repeat_n(from, text.len()));
+    }
+
+    /// Maps every character of the output to the character index of the
+    /// input it came from, with an extra entry for the end of the input.
+    pub fn char_map(&self, input: &str) -> Vec<usize> {
+        let mut map = Vec::with_capacity(self.text.len() + 1);
+
+        // Spans only ever move forwards, so the input can be walked once.
+        let mut input_chars = input.char_indices().map(|(i, _)| i).enumerate().peekable();
+        for (i, _) in self.text.char_indices() {
+            let byte = self.spans[i].0;
+            while let Some((_, next)) = input_chars.peek() {
+                if *next >= byte {
+                    break;
+                }
+                input_chars.next();
+            }
+            map.push(input_chars.peek().map_or(0, |(index, _)| *index));
+        }
+        map.push(input.chars().count());
+
+        map
+    }
+}
+
+/// A string that has been run through a stack of transforms, keeping what
+/// is needed to map offsets in the transformed text back to the original.
+pub struct Prepared<'a> {
+    pub original: &'a str,
+    pub text: Cow<'a, str>,
+
+    /// The original byte range of every byte in the transformed text, this
+    /// is `None` when there are no transforms so offsets map to themselves.
+    spans: Option<Vec<(usize, usize)>>,
+}
+
+impl<'a> Prepared<'a> {
+    pub fn new(original: &'a str, transforms: &[Transform]) -> Self {
+        let mut prepared = Prepared {
+            original,
+            text: Cow::Borrowed(original),
+            spans: None,
+        };
+
+        for transform in transforms {
+            let output = transform.apply(&prepared.text);
+            let spans = match &prepared.spans {
+                Some(previous) => output.spans
+                    .iter()
+                    .map(|(start, end)| (previous[*start].0, previous[*end - 1].1))
+                    .collect(),
+                None => output.spans,
+            };
+
+            prepared.text = Cow::Owned(output.text);
+            prepared.spans = Some(spans);
+        }
+
+        prepared
+    }
+
+    /// Maps a range of the transformed text back to the original string.
+    pub fn original(&self, start: usize, end: usize) -> (usize, usize) {
+        let spans = match &self.spans {
+            Some(spans) => spans,
+            None => return (start, end),
+        };
+
+        if start == end {
+            let pos = spans.get(start).map_or(self.original.len(), |s| s.0);
+            return (pos, pos);
+        }
+
+        (spans[start].0, spans[end - 1].1)
+    }
+
+    /// Keeps the matches whose original range starts at or after the end of
+    /// the previous one. Transforms like `lowercase` can turn one character
+    /// into several and each of them can match on its own, but they all map
+    /// back to the same character of the original string.
+    pub fn distinct<'p, T: 'p>(
+        &'p self,
+        matches: impl Iterator<Item = T> + 'p,
+        span: impl Fn(&T) -> (usize, usize) + 'p,
+    ) -> impl Iterator<Item = T> + 'p {
+        let mut last = 0;
+        matches.filter(move |m| {
+            if self.spans.is_none() {
+                return true;
+            }
+
+            let (start, end) = span(m);
+            let (start, end) = self.original(start, end);
+            if start < last {
+                return false;
+            }
+            last = end;
+            true
+        })
+    }
+
+    /// Returns the original text of a range of the transformed text.
+    pub fn original_str(&self, start: usize, end: usize) -> &'a str {
+        let (start, end) = self.original(start, end);
+        &self.original[start..end]
+    }
+
+    /// Resolves the capture groups of a match in the transformed text to
+    /// the original string.
+    pub fn captures<'c>(&'c self, caps: &'c Captures<'c>) -> OriginalCaptures<'c> {
+        OriginalCaptures { caps, prepared: self }
+    }
+
+    /// Maps an offset of the original string to the transformed text.
+    pub fn transformed(&self, offset: usize) -> usize {
+        match &self.spans {
+            Some(spans) => spans.partition_point(|s| s.0 < offset),
+            None => offset,
+        }
+    }
+}
+
+/// Capture groups of a match in the transformed text, resolved to the text
+/// of the original string.
+pub struct OriginalCaptures<'a> {
+    caps: &'a Captures<'a>,
+    prepared: &'a Prepared<'a>,
+}
+
+impl OriginalCaptures<'_> {
+    /// The amount of groups in the pattern, including the whole match.
+    pub fn len(&self) -> usize {
+        self.caps.len()
+    }
+
+    fn resolve(&self, span: Option<(usize, usize)>) -> Option<&str> {
+        span.map(|(start, end)| self.prepared.original_str(start, end))
+    }
+}
+
+impl Groups for OriginalCaptures<'_> {
+    fn index(&self, i: usize) -> Option<&str> {
+        self.resolve(self.caps.get(i))
+    }
+
+    fn name(&self, name: &str) -> Option<&str> {
+        self.resolve(self.caps.name(name))
+    }
+}
+
+pub fn strip_ansi(input: &str) -> Output {
+    let mut output = Output::with_capacity(input.len());
+
+    let mut chars = input.char_indices().peekable();
+    while let Some((i, ch)) = chars.next() {
+        let sequence = match ch {
+            // Only characters that can follow an ESC are taken as part of the
+            // sequence, a stray ESC is dropped on its own.
+            '\x1b' => match chars.peek().map(|(_, c)| *c) {
+                Some(c @ ('[' | ']' | 'P' | 'X' | '^' | '_')) => {
+                    chars.next();
+                    match c {
+                        '[' | ']' => c,
+                        _ => 'P',
+                    }
+                },
+
+                // `ESC ( B` style sequences have intermediate bytes before
+                // the final one.
+                Some(c) if ('\x20'..='\x2f').contains(&c) => {
+                    while matches!(chars.peek(), Some((_, c)) if ('\x20'..='\x2f').contains(c)) {
+                        chars.next();
+                    }
+                    if matches!(chars.peek(), Some((_, c)) if is_final(*c)) {
+                        chars.next();
+                    }
+                    continue;
+                },
+
+                // Everything else is a single final character, like `ESC 7`.
+                Some(c) if is_final(c) => {
+                    chars.next();
+                    continue;
+                },
+                _ => continue,
+            },
+            '\u{9b}' => '[',
+            _ => {
+                output.push(&input[i..i + ch.len_utf8()], (i, i + ch.len_utf8()));
+                continue;
+            },
+        };
+
+        match sequence {
+            // CSI sequences end at the first byte in the `@` to `~` range.
+            '[' => {
+                for (_, c) in chars.by_ref() {
+                    if ('\x40'..='\x7e').contains(&c) {
+                        break;
+                    }
+                }
+            },
+
+            // OSC, DCS and the other string sequences end with the string
+            // terminator `ESC \`, OSC may also end with BEL.
+            kind => {
+                while let Some((_, c)) = chars.next() {
+                    if c == '\x07' && kind == ']' {
+                        break;
+                    }
+                    if c == '\x1b' && matches!(chars.peek(), Some((_, '\\'))) {
+                        chars.next();
+                        break;
+                    }
+                }
+            },
+        }
+    }
+
+    output
+}
+
+/// If `c` can end an escape sequence started by a plain ESC.
+fn is_final(c: char) -> bool {
+    ('\x30'..='\x7e').contains(&c)
+}
+
+pub fn strip_control(input: &str) -> Output {
+    let mut output = Output::with_capacity(input.len());
+
+    for (i, ch) in input.char_indices() {
+        if ch.is_control() && !matches!(ch, '\t' | '\n' | '\r') {
+            continue;
+        }
+        output.push(&input[i..i + ch.len_utf8()], (i, i + ch.len_utf8()));
+    }
+
+    output
+}
+
+pub fn collapse_ws(input: &str) -> Output {
+    let mut output = Output::with_capacity(input.len());
+
+    let mut run_start = None;
+    for (i, ch) in input.char_indices() {
+        if ch.is_whitespace() {
+            run_start.get_or_insert(i);
+            continue;
+        }
+
+        if let Some(start) = run_start.take() {
+            output.push(" ", (start, i));
+        }
+        output.push(&input[i..i + ch.len_utf8()], (i, i + ch.len_utf8()));
+    }
+
+    if let Some(start) = run_start {
+        output.push(" ", (start, input.len()));
+    }
+
+    output
+}
+
+pub fn lowercase(input: &str) -> Output {
+    let mut output = Output::with_capacity(input.len());
+
+    let mut buf = [0; 4];
+    for (i, ch) in input.char_indices() {
+        let span = (i, i + ch.len_utf8());
+        for lower in ch.to_lowercase() {
+            output.push(lower.encode_utf8(&mut buf), span);
+        }
+    }
+
+    output
+}
@@ -0,0 +1,61 @@
+use pyo3::prelude::*;
+
+use regex::Regex;
+
+use std::time::{Duration, Instant};
+
+use crate::limits::Limits;
+use crate::metrics;
+
+
+/// The time spent matching a single member of a set and how often it
+/// matched.
+pub struct MemberProfile {
+    pub index: usize,
+    pub time: Duration,
+    pub hits: usize,
+}
+
+pub struct Profile {
+    /// The amount of texts that were timed.
+    pub texts: usize,
+
+    /// Every member, the slowest first.
+    pub members: Vec<MemberProfile>,
+}
+
+/// Times every member on its own against every `every`th text.
+///
+/// Members are matched one at a time rather than as a set, so the time of
+/// each one is known, which makes the total higher than matching the whole
+/// set at once but keeps how the members rank against each other.
+pub fn run<'a>(
+    members: &[Regex],
+    texts: impl Iterator<Item = &'a str>,
+    every: usize,
+    limits: &Limits,
+) -> PyResult<Profile> {
+    let mut profiles: Vec<MemberProfile> = (0..members.len())
+        .map(|index| MemberProfile { index, time: Duration::ZERO, hits: 0 })
+        .collect();
+
+    let mut timed = 0;
+    for text in texts.step_by(every.max(1)) {
+        limits.start(text.len())?;
+        timed += 1;
+
+        for (member, profile) in members.iter().zip(profiles.iter_mut()) {
+            let start = Instant::now();
+            let matched = member.is_match(text);
+            profile.time += start.elapsed();
+
+            if matched {
+                profile.hits += 1;
+                metrics::MATCHES.inc();
+            }
+        }
+    }
+
+    profiles.sort_by(|a, b| b.time.cmp(&a.time).then(a.index.cmp(&b.index)));
+    Ok(Profile { texts: timed, members: profiles })
+}
@@ -0,0 +1,252 @@
+use pyo3::prelude::*;
+use pyo3::exceptions::PyValueError;
+use pyo3::types::{PyDict, PyList};
+
+
+/// How a result is laid out.
+#[derive(Clone, Copy)]
+pub enum Kind {
+    /// A dict with a fixed set of keys.
+    Dict,
+
+    /// A tuple, its fields are in positional order.
+    Tuple,
+
+    /// A dict with any keys, described by a `key` and a `value` field.
+    Mapping,
+
+    /// A JSON object written as a line of text.
+    Jsonl,
+}
+
+impl Kind {
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Dict => "dict",
+            Kind::Tuple => "tuple",
+            Kind::Mapping => "mapping",
+            Kind::Jsonl => "jsonl",
+        }
+    }
+}
+
+/// The shape of a structured result, with field types written like the
+/// `regex.pyi` stub and nested results referred to by their name.
+pub struct Shape {
+    pub name: &'static str,
+    pub kind: Kind,
+    pub returned_by: &'static [&'static str],
+    pub fields: &'static [(&'static str, &'static str)],
+}
+
+const SPAN: &str = "Tuple[int, int]";
+
+pub static SHAPES: &[Shape] = &[
+    Shape {
+        name: "span",
+        kind: Kind::Tuple,
+        returned_by: &[
+            "Regex.matches",
+            "Regex.matches_codepoints",
+            "Regex.search_file",
+            "Regex.match_within_group",
+            "CidrMatcher.matches",
+            "matches",
+        ],
+        fields: &[("start", "int"), ("end", "int")],
+    },
+    Shape {
+        name: "match_id",
+        kind: Kind::Tuple,
+        returned_by: &["Regex.matches"],
+        fields: &[("start", "int"), ("end", "int"), ("id", "str")],
+    },
+    Shape {
+        name: "token",
+        kind: Kind::Tuple,
+        returned_by: &["Regex.tokenize"],
+        fields: &[("kind", "str"), ("text", "str"), ("start", "int"), ("end", "int")],
+    },
+    Shape {
+        name: "top_match",
+        kind: Kind::Tuple,
+        returned_by: &["Regex.top_matches"],
+        fields: &[("text", "str"), ("count", "int")],
+    },
+    Shape {
+        name: "template_problem",
+        kind: Kind::Dict,
+        returned_by: &["Regex.validate_template"],
+        fields: &[("kind", "str"), ("message", "str"), ("span", SPAN)],
+    },
+    Shape {
+        name: "coverage",
+        kind: Kind::Dict,
+        returned_by: &["Regex.coverage"],
+        fields: &[
+            ("texts", "int"),
+            ("matches", "int"),
+            ("branches", "List[coverage_branch]"),
+            ("groups", "List[coverage_group]"),
+        ],
+    },
+    Shape {
+        name: "coverage_branch",
+        kind: Kind::Dict,
+        returned_by: &["Regex.coverage"],
+        fields: &[("span", SPAN), ("pattern", "str"), ("hits", "int")],
+    },
+    Shape {
+        name: "coverage_group",
+        kind: Kind::Dict,
+        returned_by: &["Regex.coverage"],
+        fields: &[("index", "int"), ("name", "Optional[str]"), ("hits", "int")],
+    },
+    Shape {
+        name: "classification",
+        kind: Kind::Tuple,
+        returned_by: &["RegexSet.classify_file"],
+        fields: &[("match_count", "int"), ("first_line", "Optional[int]")],
+    },
+    Shape {
+        name: "member_profile",
+        kind: Kind::Dict,
+        returned_by: &["RegexSet.profile"],
+        fields: &[
+            ("index", "int"),
+            ("pattern", "str"),
+            ("time_ms", "float"),
+            ("share", "float"),
+            ("hits", "int"),
+            ("hit_rate", "float"),
+        ],
+    },
+    Shape {
+        name: "scan_summary",
+        kind: Kind::Dict,
+        returned_by: &["ScanSummary.to_dict"],
+        fields: &[
+            ("total", "int"),
+            ("counts", "List[int]"),
+            ("examples", "List[summary_example]"),
+            ("truncated", "bool"),
+        ],
+    },
+    Shape {
+        name: "summary_example",
+        kind: Kind::Dict,
+        returned_by: &["ScanSummary.examples", "ScanSummary.to_dict"],
+        fields: &[
+            ("pattern", "int"),
+            ("text", "str"),
+            ("span", "Optional[Tuple[int, int]]"),
+            ("line", "Optional[int]"),
+        ],
+    },
+    Shape {
+        name: "stripped",
+        kind: Kind::Tuple,
+        returned_by: &["strip_ansi", "strip_control"],
+        fields: &[("text", "str"), ("offsets", "List[int]")],
+    },
+    Shape {
+        name: "flag_scope",
+        kind: Kind::Dict,
+        returned_by: &["flag_analysis"],
+        fields: &[
+            ("directive", "str"),
+            ("directive_span", SPAN),
+            ("span", SPAN),
+            ("flags", "Dict[str, bool]"),
+        ],
+    },
+    Shape {
+        name: "policy",
+        kind: Kind::Dict,
+        returned_by: &["get_policy"],
+        fields: &[
+            ("max_groups", "Optional[int]"),
+            ("max_pattern_len", "Optional[int]"),
+            ("max_alternations", "Optional[int]"),
+        ],
+    },
+    Shape {
+        name: "stdin_match",
+        kind: Kind::Jsonl,
+        returned_by: &["scan_stdin"],
+        fields: &[("line", "int"), ("start", "int"), ("end", "int"), ("text", "str")],
+    },
+    Shape {
+        name: "comparison",
+        kind: Kind::Dict,
+        returned_by: &["compare_with_re"],
+        fields: &[
+            ("pattern", "str"),
+            ("iterations", "int"),
+            ("regex_ms", "float"),
+            ("re_ms", "float"),
+            ("speedup", "float"),
+            ("regex_matches", "int"),
+            ("re_matches", "int"),
+            ("equal", "bool"),
+            ("first_difference", "Optional[comparison_difference]"),
+        ],
+    },
+    Shape {
+        name: "comparison_difference",
+        kind: Kind::Dict,
+        returned_by: &["compare_with_re"],
+        fields: &[
+            ("index", "int"),
+            ("regex", "Optional[Tuple[int, int]]"),
+            ("re", "Optional[Tuple[int, int]]"),
+        ],
+    },
+    Shape {
+        name: "features",
+        kind: Kind::Mapping,
+        returned_by: &["features"],
+        fields: &[("key", "str"), ("value", "bool")],
+    },
+    Shape {
+        name: "lock_status",
+        kind: Kind::Mapping,
+        returned_by: &["lock_status"],
+        fields: &[("key", "str"), ("value", "bool")],
+    },
+];
+
+impl Shape {
+    pub fn to_dict(&self, py: Python) -> PyResult<PyObject> {
+        let mut fields = Vec::with_capacity(self.fields.len());
+        for (name, ty) in self.fields {
+            let field = PyDict::new(py);
+            field.set_item("name", name)?;
+            field.set_item("type", ty)?;
+            fields.push(field);
+        }
+
+        let dict = PyDict::new(py);
+        dict.set_item("name", self.name)?;
+        dict.set_item("kind", self.kind.name())?;
+        dict.set_item("returned_by", self.returned_by)?;
+        dict.set_item("fields", PyList::new(py, fields))?;
+
+        Ok(dict.into())
+    }
+}
+
+/// Finds the shape of a result by name.
+pub fn get(name: &str) -> PyResult<&'static Shape> {
+    match SHAPES.iter().find(|shape| shape.name == name) {
+        Some(shape) => Ok(shape),
+        None => {
+            let names: Vec<&str> = SHAPES.iter().map(|shape| shape.name).collect();
+            Err(PyValueError::new_err(format!(
+                "unknown result {:?}, expected one of {}",
+                name,
+                names.join(", "),
+            )))
+        },
+    }
+}
@@ -0,0 +1,140 @@
+use pyo3::prelude::*;
+use pyo3::exceptions::PyIOError;
+
+use regex::bytes::Regex;
+
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use crate::limits::Limits;
+use crate::metrics;
+
+
+/// The size of the buffer input is read into, large enough that a busy pipe
+/// is drained in a few reads.
+pub const CHUNK_SIZE: usize = 16 * 4096;
+
+/// How every match is written out.
+#[derive(Clone, Copy)]
+pub enum Format {
+    /// One JSON object per match with the `line` number, the `start` and
+    /// `end` byte offsets within the line and the matched `text`.
+    Jsonl,
+
+    /// One `line:text` row per match, like `grep -no`.
+    Text,
+}
+
+impl Format {
+    pub const ALL: [Format; 2] = [Format::Jsonl, Format::Text];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Jsonl => "jsonl",
+            Format::Text => "text",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Format::ALL.iter().copied().find(|f| f.name() == name)
+    }
+}
+
+/// Matches every line of the input and writes each match to the output,
+/// returning the amount of matches written.
+///
+/// Output is flushed whenever the next line isn't already buffered, so
+/// results of a slow stream like `tail -f` show up as soon as their line
+/// does rather than once a whole chunk has been read. Scanning stops
+/// quietly once the output is closed, like it is when piped into `head`.
+pub fn scan(
+    regex: &Regex,
+    input: impl Read,
+    output: impl Write,
+    format: Format,
+    limits: &Limits,
+) -> PyResult<usize> {
+    let mut count = 0;
+    match scan_lines(regex, input, output, format, limits, &mut count) {
+        Ok(()) => Ok(count),
+        Err(e) => match e.downcast::<PyErr>() {
+            Ok(e) => Err(e),
+            Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(count),
+            Err(e) => Err(PyIOError::new_err(e.to_string())),
+        },
+    }
+}
+
+fn scan_lines(
+    regex: &Regex,
+    input: impl Read,
+    output: impl Write,
+    format: Format,
+    limits: &Limits,
+    count: &mut usize,
+) -> io::Result<()> {
+    let mut input = BufReader::with_capacity(CHUNK_SIZE, input);
+    let mut output = io::BufWriter::with_capacity(CHUNK_SIZE, output);
+
+    let mut buf = Vec::new();
+    let mut line_number = 0;
+
+    loop {
+        buf.clear();
+        if input.read_until(b'\n', &mut buf)? == 0 {
+            break;
+        }
+        line_number += 1;
+
+        let line = crate::trim_newline(&buf);
+        limits.start(line.len()).map_err(io::Error::other)?;
+        for m in regex.find_iter(line) {
+            let text = String::from_utf8_lossy(m.as_bytes());
+            match format {
+                Format::Jsonl => {
+                    write!(output, "{{\"line\":{},\"start\":{},\"end\":{},\"text\":", line_number, m.start(), m.end())?;
+                    write_json_str(&mut output, &text)?;
+                    output.write_all(b"}\n")?;
+                },
+                Format::Text => writeln!(output, "{}:{}", line_number, text)?,
+            }
+            *count += 1;
+            metrics::MATCHES.inc();
+        }
+
+        // Reading a line that isn't buffered yet may block waiting on the
+        // other end of the pipe.
+        if !input.buffer().contains(&b'\n') {
+            output.flush()?;
+        }
+    }
+
+    output.flush()
+}
+
+fn write_json_str(output: &mut impl Write, text: &str) -> io::Result<()> {
+    output.write_all(b"\"")?;
+
+    let mut last = 0;
+    for (i, ch) in text.char_indices() {
+        let escaped = match ch {
+            '"' => "\\\"",
+            '\\' => "\\\\",
+            '\n' => "\\n",
+            '\r' => "\\r",
+            '\t' => "\\t",
+            c if (c as u32) < 0x20 => "",
+            _ => continue,
+        };
+
+        output.write_all(&text.as_bytes()[last..i])?;
+        if escaped.is_empty() {
+            write!(output, "\\u{:04x}", ch as u32)?;
+        } else {
+            output.write_all(escaped.as_bytes())?;
+        }
+        last = i + ch.len_utf8();
+    }
+    output.write_all(&text.as_bytes()[last..])?;
+
+    output.write_all(b"\"")
+}
@@ -0,0 +1,134 @@
+use pyo3::prelude::*;
+use pyo3::class::PyObjectProtocol;
+use pyo3::types::{PyDict, PyList, PyType};
+
+
+/// One of the first matches kept by a summary.
+struct Example {
+    pattern: usize,
+    text: String,
+    span: Option<(usize, usize)>,
+    line: Option<usize>,
+}
+
+/// A compact summary of a scan returned instead of the full results when
+/// `summary=N` is passed to a batch API, holding the total amount of
+/// matches, the amount each pattern had and only the first N matches as
+/// examples.
+///
+/// This keeps the cost of exposing scan results, like from a monitoring
+/// endpoint, the same however many matches a scan finds.
+#[pyclass]
+pub struct ScanSummary {
+    total: usize,
+    counts: Vec<usize>,
+    examples: Vec<Example>,
+    limit: usize,
+}
+
+impl ScanSummary {
+    /// Creates an empty summary of a scan with `patterns` patterns keeping
+    /// up to `limit` examples.
+    pub fn new(patterns: usize, limit: usize) -> Self {
+        ScanSummary {
+            total: 0,
+            counts: vec![0; patterns],
+            examples: Vec::new(),
+            limit,
+        }
+    }
+
+    /// Counts a match of `pattern`, the text is only looked at if the match
+    /// is kept as an example.
+    fn add(&mut self, pattern: usize, example: impl FnOnce() -> (String, Option<(usize, usize)>, Option<usize>)) {
+        self.total += 1;
+        self.counts[pattern] += 1;
+
+        if self.examples.len() < self.limit {
+            let (text, span, line) = example();
+            self.examples.push(Example { pattern, text, span, line });
+        }
+    }
+
+    /// Counts a match of `pattern` found at `span`.
+    pub fn add_match(&mut self, pattern: usize, span: (usize, usize), text: impl FnOnce() -> String) {
+        self.add(pattern, || (text(), Some(span), None));
+    }
+
+    /// Counts a line matched by `pattern`.
+    pub fn add_line(&mut self, pattern: usize, line: usize, text: impl FnOnce() -> String) {
+        self.add(pattern, || (text(), None, Some(line)));
+    }
+
+    fn example_dicts<'py>(&self, py: Python<'py>) -> PyResult<&'py PyList> {
+        let mut examples = Vec::with_capacity(self.examples.len());
+        for example in self.examples.iter() {
+            let dict = PyDict::new(py);
+            dict.set_item("pattern", example.pattern)?;
+            dict.set_item("text", &example.text)?;
+            dict.set_item("span", example.span)?;
+            dict.set_item("line", example.line)?;
+            examples.push(dict);
+        }
+
+        Ok(PyList::new(py, examples))
+    }
+}
+
+#[pymethods]
+impl ScanSummary {
+    /// The total amount of matches found.
+    #[getter]
+    fn total(&self) -> usize {
+        self.total
+    }
+
+    /// The amount of matches found by each pattern, by pattern index.
+    #[getter]
+    fn counts(&self) -> Vec<usize> {
+        self.counts.clone()
+    }
+
+    /// The first matches found as dicts of the `pattern` index that matched,
+    /// the matched `text` and either the `span` (start, end) of the match or
+    /// the 1-based `line` number of a matched line, whichever doesn't apply
+    /// being None.
+    #[getter]
+    fn examples(&self, py: Python) -> PyResult<PyObject> {
+        Ok(self.example_dicts(py)?.into())
+    }
+
+    /// If more matches were found than were kept as examples.
+    #[getter]
+    fn truncated(&self) -> bool {
+        self.total > self.examples.len()
+    }
+
+    /// Returns the summary as a dict of `total`, `counts`, `examples` and
+    /// `truncated`, ready to be serialized as JSON.
+    fn to_dict(&self, py: Python) -> PyResult<PyObject> {
+        let dict = PyDict::new(py);
+        dict.set_item("total", self.total)?;
+        dict.set_item("counts", &self.counts)?;
+        dict.set_item("examples", self.example_dicts(py)?)?;
+        dict.set_item("truncated", self.truncated())?;
+
+        Ok(dict.into())
+    }
+
+    /// Returns the schema of `to_dict`, the same as `regex.schema("scan_summary")`.
+    #[classmethod]
+    fn schema(_cls: &PyType, py: Python) -> PyResult<PyObject> {
+        crate::schema::get("scan_summary")?.to_dict(py)
+    }
+}
+
+#[pyproto]
+impl PyObjectProtocol for ScanSummary {
+    fn __repr__(&self) -> String {
+        format!(
+            "ScanSummary(total={}, counts={:?}, examples={})",
+            self.total, self.counts, self.examples.len(),
+        )
+    }
+}
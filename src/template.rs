@@ -0,0 +1,400 @@
+use regex::{Captures, Regex};
+
+
+/// Looks up the text of capture groups while expanding templates.
+pub trait Groups {
+    fn index(&self, i: usize) -> Option<&str>;
+    fn name(&self, name: &str) -> Option<&str>;
+}
+
+impl Groups for Captures<'_> {
+    fn index(&self, i: usize) -> Option<&str> {
+        self.get(i).map(|m| m.as_str())
+    }
+
+    fn name(&self, name: &str) -> Option<&str> {
+        Captures::name(self, name).map(|m| m.as_str())
+    }
+}
+
+/// The case conversion currently applied while expanding a template.
+#[derive(Clone, Copy)]
+enum Case {
+    Upper,
+    Lower,
+}
+
+/// A reference to a capture group, either by its index or by its name.
+enum GroupRef {
+    Index(usize),
+    Name(String),
+}
+
+/// A group reference within a template, `span` being its byte range in
+/// the template.
+struct Reference {
+    group: GroupRef,
+    span: (usize, usize),
+    braced: bool,
+}
+
+enum Piece {
+    Literal(String),
+    Group(Reference),
+
+    /// `\U` and `\L` start a case conversion, `\E` (`None`) ends it.
+    Case(Option<Case>),
+}
+
+/// A parsed replacement template.
+///
+/// Templates follow the same `$1`, `$name`, `${name}` and `$$` syntax as the
+/// Rust regex crate with the addition of the sed / perl style `\U`, `\L` and
+/// `\E` case conversion operators, `\\` produces a literal backslash.
+pub struct Template {
+    pieces: Vec<Piece>,
+
+    /// Syntax problems found while parsing, these never stop the template
+    /// from being used but likely aren't what was intended.
+    problems: Vec<Problem>,
+}
+
+#[derive(Clone, Copy)]
+pub enum ProblemKind {
+    UnknownGroup,
+    AmbiguousReference,
+    BadSyntax,
+}
+
+impl ProblemKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            ProblemKind::UnknownGroup => "unknown_group",
+            ProblemKind::AmbiguousReference => "ambiguous_reference",
+            ProblemKind::BadSyntax => "bad_syntax",
+        }
+    }
+}
+
+/// A problem with a template, `span` being its byte range in the template.
+pub struct Problem {
+    pub kind: ProblemKind,
+    pub message: String,
+    pub span: (usize, usize),
+}
+
+impl Template {
+    pub fn parse(template: &str) -> Self {
+        let mut pieces = Vec::new();
+        let mut problems = Vec::new();
+        let mut literal = String::new();
+        let mut case_active = false;
+
+        let mut rest = template;
+        while let Some(ch) = rest.chars().next() {
+            let pos = template.len() - rest.len();
+            match ch {
+                '$' => {
+                    if let Some((group, consumed)) = parse_group_ref(rest) {
+                        flush_literal(&mut pieces, &mut literal);
+                        pieces.push(Piece::Group(Reference {
+                            group,
+                            span: (pos, pos + consumed),
+                            braced: rest.starts_with("${"),
+                        }));
+                        rest = &rest[consumed..];
+                        continue;
+                    }
+
+                    // `$$` is an escaped dollar, a lone `$` is kept as is.
+                    if rest.starts_with("$$") {
+                        rest = &rest[2..];
+                    } else {
+                        let message = if rest.starts_with("${") {
+                            "unclosed '${' is treated as a literal '$'"
+                        } else {
+                            "'$' not followed by a group name is treated literally, use '$$' for a literal '$'"
+                        };
+                        problems.push(Problem {
+                            kind: ProblemKind::BadSyntax,
+                            message: message.to_string(),
+                            span: (pos, pos + 1),
+                        });
+                        rest = &rest[1..];
+                    }
+                    literal.push('$');
+                },
+                '\\' => {
+                    let case = match rest[1..].chars().next() {
+                        Some('U') => Some(Some(Case::Upper)),
+                        Some('L') => Some(Some(Case::Lower)),
+                        Some('E') => Some(None),
+                        _ => None,
+                    };
+
+                    if let Some(case) = case {
+                        if case.is_none() && !case_active {
+                            problems.push(Problem {
+                                kind: ProblemKind::BadSyntax,
+                                message: "'\\E' without a preceding '\\U' or '\\L' does nothing".to_string(),
+                                span: (pos, pos + 2),
+                            });
+                        }
+                        case_active = case.is_some();
+
+                        flush_literal(&mut pieces, &mut literal);
+                        pieces.push(Piece::Case(case));
+                        rest = &rest[2..];
+                    } else if rest.starts_with("\\\\") {
+                        literal.push('\\');
+                        rest = &rest[2..];
+                    } else {
+                        literal.push('\\');
+                        rest = &rest[1..];
+                    }
+                },
+                _ => {
+                    literal.push(ch);
+                    rest = &rest[ch.len_utf8()..];
+                },
+            }
+        }
+        flush_literal(&mut pieces, &mut literal);
+
+        Template { pieces, problems }
+    }
+
+    /// Checks the template against the groups of the compiled regex,
+    /// returning every problem found ordered by their position.
+    pub fn validate(&self, regex: &Regex, template: &str) -> Vec<Problem> {
+        let mut problems: Vec<Problem> = self.problems
+            .iter()
+            .map(|p| Problem { kind: p.kind, message: p.message.clone(), span: p.span })
+            .collect();
+
+        let exists = |group: &GroupRef| match group {
+            GroupRef::Index(i) => *i < regex.captures_len(),
+            GroupRef::Name(name) => regex.capture_names().any(|n| n == Some(name.as_str())),
+        };
+
+        for piece in self.pieces.iter() {
+            let reference = match piece {
+                Piece::Group(reference) => reference,
+                _ => continue,
+            };
+
+            let text = &template[reference.span.0..reference.span.1];
+            if let GroupRef::Name(name) = &reference.group {
+                if name.is_empty() {
+                    problems.push(Problem {
+                        kind: ProblemKind::BadSyntax,
+                        message: format!("{} is an empty group reference", text),
+                        span: reference.span,
+                    });
+                    continue;
+                }
+            }
+
+            if exists(&reference.group) {
+                continue;
+            }
+
+            // `$1st` refers to a group named `1st` rather than group 1
+            // followed by `st`, which is rarely what was meant.
+            let name = &text[1..];
+            let shorter = (1..name.len())
+                .rev()
+                .filter(|end| name.is_char_boundary(*end))
+                .map(|end| &name[..end])
+                .find(|prefix| exists(&group_ref(prefix)));
+
+            let problem = match shorter {
+                Some(prefix) if !reference.braced => Problem {
+                    kind: ProblemKind::AmbiguousReference,
+                    message: format!(
+                        "{} refers to the unknown group {:?}, use ${{{}}}{} to reference group {:?} followed by text",
+                        text, name, prefix, &name[prefix.len()..], prefix,
+                    ),
+                    span: reference.span,
+                },
+                _ => Problem {
+                    kind: ProblemKind::UnknownGroup,
+                    message: match &reference.group {
+                        GroupRef::Index(i) => format!(
+                            "{} references group {} but the pattern only has {} groups",
+                            text, i, regex.captures_len() - 1,
+                        ),
+                        GroupRef::Name(name) => format!(
+                            "{} references the unknown group {:?}", text, name,
+                        ),
+                    },
+                    span: reference.span,
+                },
+            };
+            problems.push(problem);
+        }
+
+        problems.sort_by_key(|p| p.span);
+        problems
+    }
+
+    /// Expands the template with the given captures, appending the result
+    /// to `dst`.
+    pub fn expand(&self, caps: &impl Groups, dst: &mut String) {
+        let mut case = None;
+        for piece in self.pieces.iter() {
+            match piece {
+                Piece::Literal(text) => push_cased(dst, text, case),
+                Piece::Group(reference) => {
+                    let text = match &reference.group {
+                        GroupRef::Index(i) => caps.index(*i),
+                        GroupRef::Name(name) => caps.name(name),
+                    };
+                    if let Some(text) = text {
+                        push_cased(dst, text, case);
+                    }
+                },
+                Piece::Case(new) => case = *new,
+            }
+        }
+    }
+}
+
+fn flush_literal(pieces: &mut Vec<Piece>, literal: &mut String) {
+    if !literal.is_empty() {
+        pieces.push(Piece::Literal(std::mem::take(literal)));
+    }
+}
+
+/// Parses a `$name` or `${name}` group reference at the start of `rest`,
+/// returning the reference and the amount of bytes it takes up.
+fn parse_group_ref(rest: &str) -> Option<(GroupRef, usize)> {
+    let after = &rest[1..];
+
+    let (name, consumed) = if let Some(braced) = after.strip_prefix('{') {
+        let end = braced.find('}')?;
+        (&braced[..end], end + 3)
+    } else {
+        let end = after
+            .find(|c: char| !(c == '_' || c.is_ascii_alphanumeric()))
+            .unwrap_or(after.len());
+        if end == 0 {
+            return None;
+        }
+        (&after[..end], end + 1)
+    };
+
+    Some((group_ref(name), consumed))
+}
+
+fn group_ref(name: &str) -> GroupRef {
+    match name.parse::<usize>() {
+        Ok(i) => GroupRef::Index(i),
+        Err(_) => GroupRef::Name(name.to_string()),
+    }
+}
+
+fn push_cased(dst: &mut String, text: &str, case: Option<Case>) {
+    match case {
+        Some(Case::Upper) => dst.push_str(&text.to_uppercase()),
+        Some(Case::Lower) => dst.push_str(&text.to_lowercase()),
+        None => dst.push_str(text),
+    }
+}
+
+enum FormatPiece {
+    Literal(String),
+    Group(usize),
+}
+
+/// A format template rendering each match to a line of output, using
+/// `{1}` / `{name}` for groups, `{0}` for the whole match and `{{` / `}}` for
+/// literal braces.
+///
+/// Group references are resolved when the template is compiled so rendering
+/// never has to look groups up by name.
+pub struct FormatTemplate {
+    pieces: Vec<FormatPiece>,
+}
+
+impl FormatTemplate {
+    pub fn compile(template: &str, regex: &Regex) -> Result<Self, String> {
+        let mut pieces = Vec::new();
+        let mut literal = String::new();
+
+        let mut rest = template;
+        while let Some(ch) = rest.chars().next() {
+            if rest.starts_with("{{") || rest.starts_with("}}") {
+                literal.push(ch);
+                rest = &rest[2..];
+                continue;
+            }
+
+            match ch {
+                '{' => {
+                    let end = match rest.find('}') {
+                        Some(end) => end,
+                        None => return Err(format!(
+                            "unclosed '{{' at position {} in format template",
+                            crate::char_offset(template, template.len() - rest.len()),
+                        )),
+                    };
+
+                    let group = resolve_group(&rest[1..end], regex)?;
+                    if !literal.is_empty() {
+                        pieces.push(FormatPiece::Literal(std::mem::take(&mut literal)));
+                    }
+                    pieces.push(FormatPiece::Group(group));
+                    rest = &rest[end + 1..];
+                },
+                '}' => return Err(format!(
+                    "single '}}' at position {} in format template, use '}}}}' for a literal brace",
+                    crate::char_offset(template, template.len() - rest.len()),
+                )),
+                _ => {
+                    literal.push(ch);
+                    rest = &rest[ch.len_utf8()..];
+                },
+            }
+        }
+
+        if !literal.is_empty() {
+            pieces.push(FormatPiece::Literal(literal));
+        }
+
+        Ok(FormatTemplate { pieces })
+    }
+
+    /// Renders the template for the given captures, appending the result to
+    /// `dst`. Groups that didn't participate in the match render as nothing.
+    pub fn render(&self, caps: &impl Groups, dst: &mut String) {
+        for piece in self.pieces.iter() {
+            match piece {
+                FormatPiece::Literal(text) => dst.push_str(text),
+                FormatPiece::Group(i) => {
+                    if let Some(text) = caps.index(*i) {
+                        dst.push_str(text);
+                    }
+                },
+            }
+        }
+    }
+}
+
+fn resolve_group(name: &str, regex: &Regex) -> Result<usize, String> {
+    if let Ok(i) = name.parse::<usize>() {
+        if i < regex.captures_len() {
+            return Ok(i);
+        }
+
+        return Err(format!(
+            "format template references group {} but the pattern only has {} groups",
+            i, regex.captures_len() - 1,
+        ))
+    }
+
+    regex
+        .capture_names()
+        .position(|n| n == Some(name))
+        .ok_or_else(|| format!("format template references unknown group {:?}", name))
+}